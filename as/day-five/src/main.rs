@@ -1,8 +1,11 @@
+use page_rules::Rules;
 use std::{
-    collections::HashMap,
+    collections::BTreeSet,
     fs::File,
+    hash::Hash,
     io::{self, BufRead, BufReader, Lines},
     path::Path,
+    str::FromStr,
 };
 
 fn read_lines<P>(path: P) -> io::Result<std::io::Lines<BufReader<File>>>
@@ -35,51 +38,51 @@ fn parse_input(lines: Lines<BufReader<File>>) -> (Vec<String>, Vec<String>) {
     (first_part, second_part)
 }
 
-// Builds a precedence map where each entry, which is a number `n`, contains a vector
-// of the numbers which need to appear before `n` in the input vector
-fn build_precedence_map(number_orderings: Vec<String>) -> HashMap<u8, Vec<u8>> {
-    let mut precedence_map: HashMap<u8, Vec<u8>> = HashMap::new();
+// Builds a precedence map where each number `n` knows the numbers which need to appear after
+// `n` in the input vector. Page numbers can be of any type which can be parsed
+fn build_precedence_map<T>(number_orderings: Vec<String>) -> Rules<T>
+where
+    T: FromStr + Eq + Hash + Clone,
+    T::Err: std::fmt::Debug,
+{
+    let mut precedence_map = Rules::new();
 
     for line in number_orderings {
-        let numbers: Vec<u8> = line.split("|").map(|n| n.parse::<u8>().unwrap()).collect();
+        let numbers: Vec<T> = line.split("|").map(|n| n.parse::<T>().unwrap()).collect();
         assert_eq!(numbers.len(), 2);
 
-        precedence_map
-            .entry(numbers[1])
-            .and_modify(|v: &mut Vec<u8>| v.push(numbers[0]))
-            .or_insert(Vec::from([numbers[0]]));
+        precedence_map.add(numbers[0].clone(), numbers[1].clone());
     }
 
     precedence_map
 }
 
-fn find_middle_number(numbers: &Vec<u8>) -> u8 {
+fn find_middle_number<T: Clone>(numbers: &[T]) -> T {
     let middle_idx = numbers.len() / 2;
 
-    *numbers.get(middle_idx).unwrap()
+    numbers.get(middle_idx).unwrap().clone()
 }
 
-fn are_valid_page_numbers(page_numbers: &[u8], precedence_map: &HashMap<u8, Vec<u8>>) -> bool {
-    for (idx, number) in page_numbers.iter().enumerate() {
-        match precedence_map.get(number) {
-            Some(numbers_with_precedence) => {
-                for n in &page_numbers[idx..] {
-                    if numbers_with_precedence.contains(n) {
-                        return false;
-                    }
-                }
+fn are_valid_page_numbers<T: Eq + Hash + Clone>(
+    page_numbers: &[T],
+    precedence_map: &Rules<T>,
+) -> bool {
+    let ids = precedence_map.ids(page_numbers);
+    for (idx, &id) in ids.iter().enumerate() {
+        for &following_id in &ids[idx..] {
+            if precedence_map.precedes(following_id, id) {
+                return false;
             }
-            None => continue,
         }
     }
 
     true
 }
 
-fn find_invalid_page_numbers(
-    page_numbers: Vec<Vec<u8>>,
-    precedence_map: &HashMap<u8, Vec<u8>>,
-) -> Vec<Vec<u8>> {
+fn find_invalid_page_numbers<T: Eq + Hash + Clone>(
+    page_numbers: Vec<Vec<T>>,
+    precedence_map: &Rules<T>,
+) -> Vec<Vec<T>> {
     page_numbers
         .iter()
         .filter(|numbers| !are_valid_page_numbers(numbers, precedence_map))
@@ -87,24 +90,28 @@ fn find_invalid_page_numbers(
         .collect()
 }
 
-fn find_valid_page_numbers(
-    page_numbers: Vec<Vec<u8>>,
-    precedence_map: HashMap<u8, Vec<u8>>,
-) -> Vec<Vec<u8>> {
+fn find_valid_page_numbers<T: Eq + Hash + Clone>(
+    page_numbers: Vec<Vec<T>>,
+    precedence_map: &Rules<T>,
+) -> Vec<Vec<T>> {
     page_numbers
         .iter()
-        .filter(|numbers| are_valid_page_numbers(numbers, &precedence_map))
+        .filter(|numbers| are_valid_page_numbers(numbers, precedence_map))
         .map(|v| v.to_vec())
         .collect()
 }
 
-fn parse_page_numbers(page_numbers_input: Vec<String>) -> Vec<Vec<u8>> {
-    let mut parsed_page_numbers: Vec<Vec<u8>> = Vec::new();
+fn parse_page_numbers<T>(page_numbers_input: Vec<String>) -> Vec<Vec<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Debug,
+{
+    let mut parsed_page_numbers: Vec<Vec<T>> = Vec::new();
 
     for page_numbers in page_numbers_input {
-        let page_numbers: Vec<u8> = page_numbers
+        let page_numbers: Vec<T> = page_numbers
             .split(',')
-            .map(|n| n.parse::<u8>().unwrap())
+            .map(|n| n.parse::<T>().unwrap())
             .collect();
         parsed_page_numbers.push(page_numbers);
     }
@@ -112,70 +119,114 @@ fn parse_page_numbers(page_numbers_input: Vec<String>) -> Vec<Vec<u8>> {
     parsed_page_numbers
 }
 
-// Finds the indicies of two elements which need to be swapped in order to adhere to the calculated
-// precedence map.
-fn find_swap(
-    invalid_input: &mut Vec<u8>,
-    precedence_map: &HashMap<u8, Vec<u8>>,
-) -> Option<(usize, usize)> {
-    for (idx, current_number) in invalid_input.iter().enumerate() {
-        let following_nums = &invalid_input[idx + 1..];
-        for (following_index, following_number) in following_nums.iter().enumerate() {
-            match precedence_map.get(current_number) {
-                Some(numbers_with_precedence) => {
-                    if numbers_with_precedence.contains(following_number) {
-                        // Cannot swap based on the index of the following-num slice, need the
-                        // index of the element in the original list.
-                        let following_index_in_original_input = following_index + idx + 1;
-                        return Some((idx, following_index_in_original_input));
-                    }
-                }
-                None => continue,
-            }
+// Finds a cycle among the remaining page indices, which all still have a page with precedence
+// among them, by following the precedences backwards until a page repeats. The cycle is
+// returned in order, each page needing to appear before the next one and the last one before
+// the first one
+fn find_cycle<T: Eq + Hash + Clone>(remaining: &[usize], precedence_map: &Rules<T>) -> Vec<usize> {
+    let mut path = vec![remaining[0]];
+    loop {
+        let current = path[path.len() - 1];
+        let previous = *remaining
+            .iter()
+            .find(|&&id| precedence_map.precedes(id, current))
+            .unwrap();
+        if let Some(start) = path.iter().position(|&id| id == previous) {
+            let mut cycle = path.split_off(start);
+            cycle.reverse();
+            return cycle;
         }
+        path.push(previous);
     }
-
-    None
 }
 
 // modifies the given input values, so that they are ordered correctly with respect to the given
-// precedence map.
-fn correct_ordering(invalid_input: &mut Vec<u8>, precedence_map: &HashMap<u8, Vec<u8>>) {
-    while let Some((idx1, idx2)) = find_swap(invalid_input, precedence_map) {
-        println!("Swappinng indexes {} and {}", idx1, idx2);
-        invalid_input.swap(idx1, idx2);
+// precedence map. This is a topological sort of the precedences between the input values, where
+// the earliest value in the input is picked whenever several could go next. If the precedences
+// contradict each other, the input is left as is and the numbers forming a cycle are returned
+fn correct_ordering<T: Eq + Hash + Clone>(
+    invalid_input: &mut [T],
+    precedence_map: &Rules<T>,
+) -> Result<(), Vec<T>> {
+    let ids = precedence_map.ids(invalid_input);
+    // for every value, how many of the other values still need to be placed before it
+    let mut waiting_for: Vec<usize> = ids
+        .iter()
+        .map(|&id| {
+            ids.iter()
+                .filter(|&&other| precedence_map.precedes(other, id))
+                .count()
+        })
+        .collect();
+    let mut ready: BTreeSet<usize> = (0..ids.len())
+        .filter(|&idx| waiting_for[idx] == 0)
+        .collect();
+
+    let mut ordered = Vec::with_capacity(ids.len());
+    while let Some(idx) = ready.pop_first() {
+        ordered.push(idx);
+        for (following_idx, &following_id) in ids.iter().enumerate() {
+            if precedence_map.precedes(ids[idx], following_id) {
+                waiting_for[following_idx] -= 1;
+                if waiting_for[following_idx] == 0 {
+                    ready.insert(following_idx);
+                }
+            }
+        }
     }
+
+    if ordered.len() < ids.len() {
+        let remaining: Vec<usize> = (0..ids.len())
+            .filter(|idx| !ordered.contains(idx))
+            .map(|idx| ids[idx])
+            .collect();
+        let cycle = find_cycle(&remaining, precedence_map);
+        return Err(cycle
+            .into_iter()
+            .map(|id| precedence_map.page(id).clone())
+            .collect());
+    }
+
+    let ordered: Vec<T> = ordered
+        .into_iter()
+        .map(|idx| invalid_input[idx].clone())
+        .collect();
+    invalid_input.clone_from_slice(&ordered);
+    Ok(())
 }
 
 fn main() {
     let lines = read_lines("input.txt").unwrap();
     let (first_part, second_part) = parse_input(lines);
 
-    let page_numbers = parse_page_numbers(second_part);
+    let page_numbers: Vec<Vec<u32>> = parse_page_numbers(second_part);
     let precedence_map = build_precedence_map(first_part);
 
     // first part
-    //let valid_page_numbers = find_valid_page_numbers(page_numbers, precedence_map);
+    let valid_page_numbers = find_valid_page_numbers(page_numbers.clone(), &precedence_map);
 
-    //let total: u32 = valid_page_numbers
-    //    .iter()
-    //    .map(|nums| find_middle_number(&nums.to_vec()) as u32)
-    //    .sum();
+    let total: u32 = valid_page_numbers
+        .iter()
+        .map(|nums| find_middle_number(nums))
+        .sum();
 
     // 7074
-    //println!("{}", total);
+    println!("{}", total);
 
     // second part
     let mut invalid_page_numbers = find_invalid_page_numbers(page_numbers, &precedence_map);
-    let corrected_sum: u32 = invalid_page_numbers
+    let corrected_sum: Result<u32, Vec<u32>> = invalid_page_numbers
         .iter_mut()
         .map(|nums| {
-            correct_ordering(nums, &precedence_map);
-            find_middle_number(nums) as u32
+            correct_ordering(nums, &precedence_map)?;
+            Ok(find_middle_number(nums))
         })
         .sum();
 
-    println!("{}", corrected_sum);
+    match corrected_sum {
+        Ok(sum) => println!("{}", sum),
+        Err(cycle) => println!("Cannot correct ordering, rules form a cycle: {:?}", cycle),
+    }
 }
 
 #[cfg(test)]
@@ -184,33 +235,67 @@ mod tests {
 
     #[test]
     fn test_correct_ordering() {
-        let mut page_numbers: Vec<Vec<u8>> = Vec::new();
-        page_numbers.push(vec![1, 3, 2]);
+        let mut page_numbers: Vec<Vec<u8>> = vec![vec![1, 3, 2]];
 
-        let mut precedence_map = HashMap::new();
-        precedence_map.insert(2, vec![1]);
-        precedence_map.insert(3, vec![1, 2]);
-        precedence_map.insert(4, vec![1, 2, 3]);
+        let mut precedence_map = Rules::new();
+        precedence_map.add(1, 2);
+        precedence_map.add(1, 3);
+        precedence_map.add(2, 3);
+        precedence_map.add(1, 4);
+        precedence_map.add(2, 4);
+        precedence_map.add(3, 4);
 
-        correct_ordering(&mut page_numbers[0], &precedence_map);
+        correct_ordering(&mut page_numbers[0], &precedence_map).unwrap();
 
         println!("Resulting vector: {:?}", page_numbers[0]);
+        assert_eq!(page_numbers[0], vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_correct_ordering_with_cycle() {
+        let mut page_numbers = vec![4, 3, 2, 1];
+
+        let mut precedence_map = Rules::new();
+        precedence_map.add(1, 2);
+        precedence_map.add(2, 3);
+        precedence_map.add(3, 1);
+        precedence_map.add(1, 4);
+
+        let cycle = correct_ordering(&mut page_numbers, &precedence_map).unwrap_err();
+
+        assert_eq!(cycle, vec![2, 3, 1]);
+        assert_eq!(page_numbers, vec![4, 3, 2, 1]);
     }
 
     #[test]
     fn test_find_valid_page_numbers() {
-        let mut page_numbers = Vec::new();
-        page_numbers.push(vec![1, 2, 3]);
-        page_numbers.push(vec![1, 2, 3, 4]);
-        page_numbers.push(vec![1, 3, 2]);
+        let page_numbers = vec![vec![1, 2, 3], vec![1, 2, 3, 4], vec![1, 3, 2]];
 
-        let mut precedence_map = HashMap::new();
-        precedence_map.insert(2, vec![1]);
-        precedence_map.insert(3, vec![1, 2]);
-        precedence_map.insert(4, vec![1, 2, 3]);
+        let mut precedence_map = Rules::new();
+        precedence_map.add(1, 2);
+        precedence_map.add(1, 3);
+        precedence_map.add(2, 3);
+        precedence_map.add(1, 4);
+        precedence_map.add(2, 4);
+        precedence_map.add(3, 4);
 
-        let valids = find_valid_page_numbers(page_numbers, precedence_map);
+        let valids = find_valid_page_numbers(page_numbers, &precedence_map);
 
         assert_eq!(valids.len(), 2);
     }
+
+    #[test]
+    fn test_correct_ordering_with_string_pages() {
+        let orderings = vec![
+            "97|13".to_string(),
+            "97|75".to_string(),
+            "75|13".to_string(),
+        ];
+        let precedence_map: Rules<String> = build_precedence_map(orderings);
+
+        let mut page_numbers: Vec<String> = vec!["97".into(), "13".into(), "75".into()];
+        correct_ordering(&mut page_numbers, &precedence_map).unwrap();
+
+        assert_eq!(page_numbers, vec!["97", "75", "13"]);
+    }
 }
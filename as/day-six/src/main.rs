@@ -1,7 +1,8 @@
 #![allow(unused)]
 
+use direction_masks::DirectionMasks;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashSet,
     fmt::Debug,
     fs::File,
     io::{BufRead, BufReader},
@@ -21,7 +22,7 @@ impl Position {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum Direction {
     Up,
     Right,
@@ -30,6 +31,30 @@ enum Direction {
 }
 
 impl Direction {
+    fn from_glyph(glyph: char) -> Option<Self> {
+        match glyph {
+            '^' => Some(Direction::Up),
+            '>' => Some(Direction::Right),
+            'v' => Some(Direction::Down),
+            '<' => Some(Direction::Left),
+            _ => None,
+        }
+    }
+
+    // Change in row and column for one step in this direction
+    fn vector(self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Right => (0, 1),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
     fn turn_right(self) -> Self {
         match self {
             Direction::Up => Direction::Right,
@@ -40,6 +65,7 @@ impl Direction {
     }
 }
 
+// How one straight walk of the guard ends
 enum MovementResult {
     NewPosition {
         new_position: Position,
@@ -49,11 +75,15 @@ enum MovementResult {
     LoopFound,
 }
 
-#[derive(Clone)]
 struct Map {
     tiles: Vec<Vec<char>>,
-    initial_position: Position,
-    initial_direction: Direction,
+    // Starting position and direction of every guard, in reading order
+    guards: Vec<(Position, Direction)>,
+    // An obstacle placed on top of the tiles, so that the map does not need to be copied when
+    // trying out different placements
+    extra_obstacle: Option<Position>,
+    // Length of the longest row, so every position has its own cell in a `DirectionMasks`
+    cols: usize,
 }
 
 impl Map {
@@ -61,299 +91,256 @@ impl Map {
     where
         P: AsRef<Path> + Debug,
     {
-        let mut initial_position = None;
-        let mut parsed_tiles: Vec<Vec<char>> = Vec::new();
-
         let file = File::open(input_file).expect("Failed to open input file!");
 
         let lines = BufReader::new(file).lines();
 
-        for (row_num, contents) in lines.flatten().enumerate() {
-            if let Some(col_num) = contents.find('^') {
-                initial_position = Some(Position::new(row_num, col_num));
+        Self::from_tiles(
+            lines
+                .map_while(Result::ok)
+                .map(|l| l.chars().collect())
+                .collect(),
+        )
+    }
+
+    fn from_tiles(tiles: Vec<Vec<char>>) -> Self {
+        let mut guards = Vec::new();
+
+        for (row_num, row) in tiles.iter().enumerate() {
+            for (col_num, &tile) in row.iter().enumerate() {
+                if let Some(direction) = Direction::from_glyph(tile) {
+                    guards.push((Position::new(row_num, col_num), direction));
+                }
             }
-            parsed_tiles.push(contents.chars().collect());
         }
 
-        assert!(initial_position != None);
+        assert!(!guards.is_empty());
 
+        let cols = tiles.iter().map(|row| row.len()).max().unwrap_or(0);
         Self {
-            tiles: parsed_tiles,
-            initial_position: initial_position.unwrap(),
-            initial_direction: Direction::Up,
+            tiles,
+            guards,
+            extra_obstacle: None,
+            cols,
         }
     }
 
-    fn char_at(&self, row: usize, col: usize) -> char {
-        let row: &Vec<char> = self.tiles.get(row).unwrap();
-        *row.get(col).unwrap()
+    // An empty grid to keep track of the directions in which each position has been visited
+    fn direction_masks(&self) -> DirectionMasks {
+        DirectionMasks::new(self.tiles.len() * self.cols)
     }
 
-    fn set_char_to(&mut self, row: usize, col: usize, set_to: char) {
-        self.tiles[row][col] = set_to;
+    // The index of the position in a `DirectionMasks`
+    fn cell(&self, position: Position) -> usize {
+        position.row * self.cols + position.col
     }
-}
-
-fn move_up_from(
-    starting_position: Position,
-    direction: Direction,
-    map: &Map,
-    positions_visited: &mut HashMap<Position, Vec<Direction>>,
-) -> MovementResult {
-    let mut current_row = starting_position.row;
-
-    while current_row > 0 {
-        let next_row = current_row - 1;
-        if map.char_at(next_row, starting_position.col) == '#' {
-            return MovementResult::NewPosition {
-                new_position: Position::new(current_row, starting_position.col),
-                new_direction: direction.turn_right(),
-            };
-        }
-
-        let new_position = Position::new(current_row, starting_position.col);
-
-        if found_loop(&new_position, &direction, positions_visited) {
-            return MovementResult::LoopFound;
-        }
 
-        positions_visited
-            .entry(new_position)
-            .and_modify(|dirs| dirs.push(direction))
-            .or_insert(vec![direction]);
-        current_row = next_row;
+    fn char_at(&self, row: usize, col: usize) -> char {
+        let row: &Vec<char> = self.tiles.get(row).unwrap();
+        *row.get(col).unwrap()
     }
 
-    positions_visited
-        .entry(Position::new(current_row, starting_position.col))
-        .and_modify(|dirs| dirs.push(direction))
-        .or_insert(vec![direction]);
-    MovementResult::OffTheMap
-}
-
-fn move_down_from(
-    starting_position: Position,
-    direction: Direction,
-    map: &Map,
-    positions_visited: &mut HashMap<Position, Vec<Direction>>,
-) -> MovementResult {
-    let mut current_row = starting_position.row;
-    let bottom_row_num = map.tiles.len() - 1;
-
-    while current_row < bottom_row_num {
-        let next_row = current_row + 1;
-        if map.char_at(next_row, starting_position.col) == '#' {
-            return MovementResult::NewPosition {
-                new_position: Position::new(current_row, starting_position.col),
-                new_direction: direction.turn_right(),
-            };
-        }
-
-        let new_position = Position::new(current_row, starting_position.col);
-
-        if found_loop(&new_position, &direction, positions_visited) {
-            return MovementResult::LoopFound;
-        }
-
-        positions_visited
-            .entry(new_position)
-            .and_modify(|dirs| dirs.push(direction))
-            .or_insert(vec![direction]);
-        current_row = next_row;
+    fn is_obstacle(&self, row: usize, col: usize) -> bool {
+        self.char_at(row, col) == '#' || self.extra_obstacle == Some(Position::new(row, col))
     }
 
-    positions_visited
-        .entry(Position::new(current_row, starting_position.col))
-        .and_modify(|dirs| dirs.push(direction))
-        .or_insert(vec![direction]);
-    MovementResult::OffTheMap
-}
-
-fn move_right_from(
-    starting_position: Position,
-    direction: Direction,
-    map: &Map,
-    positions_visited: &mut HashMap<Position, Vec<Direction>>,
-) -> MovementResult {
-    let mut current_col = starting_position.col;
-    let rightmost_col_num = map.tiles.get(0).unwrap().len() - 1;
-
-    while current_col < rightmost_col_num {
-        let next_col = current_col + 1;
-        if map.char_at(starting_position.row, next_col) == '#' {
-            return MovementResult::NewPosition {
-                new_position: Position::new(starting_position.row, current_col),
-                new_direction: direction.turn_right(),
-            };
-        }
-
-        let new_position = Position::new(starting_position.row, current_col);
-
-        if found_loop(&new_position, &direction, positions_visited) {
-            return MovementResult::LoopFound;
-        }
-
-        positions_visited
-            .entry(new_position)
-            .and_modify(|dirs| dirs.push(direction))
-            .or_insert(vec![direction]);
-        current_col = next_col;
+    // The position one step away in the given direction, if it is still on the map
+    fn step(&self, position: Position, direction: Direction) -> Option<Position> {
+        let (row_change, col_change) = direction.vector();
+        let row = position.row.checked_add_signed(row_change)?;
+        let col = position.col.checked_add_signed(col_change)?;
+        let on_map = row < self.tiles.len() && col < self.tiles[row].len();
+        on_map.then_some(Position::new(row, col))
     }
-
-    positions_visited
-        .entry(Position::new(starting_position.row, current_col))
-        .and_modify(|dirs| dirs.push(direction))
-        .or_insert(vec![direction]);
-
-    MovementResult::OffTheMap
 }
 
-fn move_left_from(
+// Walks the guard straight ahead from the starting position until it runs into an obstacle, walks
+// off the map, or gets back to a position it has already been in walking the same direction
+fn move_from(
     starting_position: Position,
     direction: Direction,
     map: &Map,
-    positions_visited: &mut HashMap<Position, Vec<Direction>>,
+    positions_visited: &mut DirectionMasks,
 ) -> MovementResult {
-    let mut current_col = starting_position.col;
-
-    while current_col > 0 {
-        let next_col = current_col - 1;
-        if map.char_at(starting_position.row, next_col) == '#' {
-            return MovementResult::NewPosition {
-                new_position: Position::new(starting_position.row, current_col),
-                new_direction: direction.turn_right(),
-            };
-        }
-        let new_position = Position::new(starting_position.row, current_col);
+    let mut position = starting_position;
 
-        if found_loop(&new_position, &direction, positions_visited) {
+    loop {
+        if !positions_visited.insert(map.cell(position), direction.index()) {
             return MovementResult::LoopFound;
         }
 
-        positions_visited
-            .entry(new_position)
-            .and_modify(|dirs| dirs.push(direction))
-            .or_insert(vec![direction]);
-        current_col = next_col;
+        match map.step(position, direction) {
+            None => return MovementResult::OffTheMap,
+            Some(next_position) if map.is_obstacle(next_position.row, next_position.col) => {
+                return MovementResult::NewPosition {
+                    new_position: position,
+                    new_direction: direction.turn_right(),
+                };
+            }
+            Some(next_position) => position = next_position,
+        }
     }
-
-    positions_visited
-        .entry(Position::new(starting_position.row, current_col))
-        .and_modify(|dirs| dirs.push(direction))
-        .or_insert(vec![direction]);
-
-    MovementResult::OffTheMap
 }
 
-fn found_loop(
-    position: &Position,
-    direction: &Direction,
-    positions_visited: &mut HashMap<Position, Vec<Direction>>,
-) -> bool {
-    if let Some(directions) = positions_visited.get(position) {
-        return directions.contains(direction);
-    }
-
-    false
+// How the whole patrol of a guard ends
+#[derive(Debug, PartialEq, Eq)]
+enum PatrolResult {
+    LeftTheMap,
+    // The guard walks in a loop
+    NeverExits,
 }
 
-fn move_from(
-    starting_position: Position,
-    initial_direction: Direction,
+fn patrol(
+    mut position: Position,
+    mut direction: Direction,
     map: &Map,
-    positions_visited: &mut HashMap<Position, Vec<Direction>>,
-) -> MovementResult {
-    match initial_direction {
-        Direction::Up => move_up_from(starting_position, initial_direction, map, positions_visited),
-        Direction::Right => {
-            move_right_from(starting_position, initial_direction, map, positions_visited)
-        }
-        Direction::Down => {
-            move_down_from(starting_position, initial_direction, map, positions_visited)
-        }
-        Direction::Left => {
-            move_left_from(starting_position, initial_direction, map, positions_visited)
+    positions_visited: &mut DirectionMasks,
+) -> PatrolResult {
+    loop {
+        match move_from(position, direction, map, positions_visited) {
+            MovementResult::NewPosition {
+                new_position,
+                new_direction,
+            } => {
+                position = new_position;
+                direction = new_direction;
+            }
+            MovementResult::OffTheMap => return PatrolResult::LeftTheMap,
+            MovementResult::LoopFound => return PatrolResult::NeverExits,
         }
     }
 }
 
-fn generate_possible_obstacle_placements(original_map: &Map) -> Vec<Map> {
-    let mut maps: Vec<Map> = Vec::new();
-
-    for (row_num, row_contents) in original_map.tiles.iter().enumerate() {
-        for (col_num, col_contents) in row_contents.iter().enumerate() {
-            if *col_contents != '^' && *col_contents != '#' {
-                let mut modified_map = original_map.clone();
-                modified_map.set_char_to(row_num, col_num, '#');
-                maps.push(modified_map);
+// An obstacle only makes a difference if the guard runs into it, so the only placements worth
+// trying are on the guard's original route. Until the guard first gets to the obstacle, it walks
+// the original route, so for every placement this returns the position and direction of the guard
+// right in front of it as well, to start walking from there
+fn find_possible_obstacle_placements(
+    map: &Map,
+    initial_position: Position,
+    initial_direction: Direction,
+) -> Vec<(Position, Position, Direction)> {
+    let mut placements = Vec::new();
+    let mut seen = HashSet::from([initial_position]);
+    let mut position = initial_position;
+    let mut direction = initial_direction;
+
+    // A guard walking in a loop comes back to a known position and direction, where the walk
+    // stops
+    let mut states = HashSet::from([(position, direction)]);
+
+    while let Some(next_position) = map.step(position, direction) {
+        if map.is_obstacle(next_position.row, next_position.col) {
+            direction = direction.turn_right();
+        } else {
+            if seen.insert(next_position) {
+                placements.push((next_position, position, direction));
             }
+            position = next_position;
+        }
+        if !states.insert((position, direction)) {
+            break;
         }
     }
 
-    maps
+    placements
 }
 
 fn main() {
     let map = Map::from_file("input.txt");
-    let mut position = map.initial_position;
-    let mut direction = map.initial_direction.clone();
-
-    // Map from a position to the directions in which the guard has moved when visiting the position
-    let mut positions_visited: HashMap<Position, Vec<Direction>> = HashMap::new();
 
-    loop {
-        match move_from(position, direction, &map, &mut positions_visited) {
-            MovementResult::NewPosition {
-                new_position,
-                new_direction,
-            } => {
-                position = new_position;
-                direction = new_direction;
-            }
-            MovementResult::OffTheMap => break,
-            MovementResult::LoopFound => todo!(),
-        }
+    let mut all_positions_visited = map.direction_masks();
+    let mut any_guard_loops = false;
+
+    for (guard, &(position, direction)) in map.guards.iter().enumerate() {
+        // The directions in which the guard has moved when visiting each position
+        let mut positions_visited = map.direction_masks();
+        let result = patrol(position, direction, &map, &mut positions_visited);
+        let loops = result == PatrolResult::NeverExits;
+
+        println!(
+            "Number of positions visited by guard {}: {}{}",
+            guard,
+            positions_visited.len(),
+            if loops { " (guard never exits)" } else { "" }
+        );
+        all_positions_visited.union_with(&positions_visited);
+        any_guard_loops |= loops;
     }
 
     println!(
-        "Number of positions visited by the guard on original map: {}",
-        positions_visited.len()
-    );
-
-    // Second star, release build recommended!
-    let possible_placements = generate_possible_obstacle_placements(&map);
-    println!(
-        "Possible positions at which an obstacle can be placed: {}",
-        possible_placements.len()
+        "Number of positions visited by the guards on original map: {}",
+        all_positions_visited.len()
     );
+    println!("Any guard walks in a loop: {}", any_guard_loops);
+
+    // Second star
+    let mut map = map;
+    let starting_positions: HashSet<Position> = map.guards.iter().map(|&(p, _)| p).collect();
+    let mut loop_placements: HashSet<Position> = HashSet::new();
+    // Cleared for every placement instead of allocating a new one
+    let mut positions_visited = map.direction_masks();
+
+    for (guard, &(initial_position, initial_direction)) in map.guards.clone().iter().enumerate() {
+        map.extra_obstacle = None;
+        let possible_placements =
+            find_possible_obstacle_placements(&map, initial_position, initial_direction);
+        println!(
+            "Possible positions at which an obstacle can be placed for guard {}: {}",
+            guard,
+            possible_placements.len()
+        );
+
+        println!("Searching for placements resulting in a loop...");
+        for (obstacle, position, direction) in possible_placements {
+            if starting_positions.contains(&obstacle) {
+                continue;
+            }
+            map.extra_obstacle = Some(obstacle);
+            positions_visited.clear();
 
-    println!("Searching for placements resulting in a loop...");
-    let mut loop_placements = 0;
-    'outer: for map in possible_placements {
-        let mut position = map.initial_position;
-        let mut direction = map.initial_direction.clone();
-        let mut positions_visited: HashMap<Position, Vec<Direction>> = HashMap::new();
-
-        loop {
-            match move_from(position, direction, &map, &mut positions_visited) {
-                MovementResult::NewPosition {
-                    new_position,
-                    new_direction,
-                } => {
-                    position = new_position;
-                    direction = new_direction;
-                }
-                MovementResult::OffTheMap => break,
-                MovementResult::LoopFound => {
-                    loop_placements += 1;
-                    break;
-                }
+            if patrol(position, direction, &map, &mut positions_visited) == PatrolResult::NeverExits
+            {
+                loop_placements.insert(obstacle);
             }
         }
     }
 
     println!(
         "Number of possible loop placements found: {}",
-        loop_placements
+        loop_placements.len()
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_from(rows: &[&str]) -> Map {
+        Map::from_tiles(rows.iter().map(|r| r.chars().collect()).collect())
+    }
+
+    #[test]
+    fn test_patrol_leaves_the_map() {
+        let map = map_from(&["....#.....", "....^....#", "..........", "........#."]);
+        let (position, direction) = map.guards[0];
+        let mut positions_visited = map.direction_masks();
+        assert_eq!(
+            patrol(position, direction, &map, &mut positions_visited),
+            PatrolResult::LeftTheMap
+        );
+        assert_eq!(positions_visited.len(), 14);
+    }
+
+    #[test]
+    fn test_patrol_never_exits() {
+        let map = map_from(&[".#..", "...#", "#<..", "..#."]);
+        let (position, direction) = map.guards[0];
+        let mut positions_visited = map.direction_masks();
+        assert_eq!(
+            patrol(position, direction, &map, &mut positions_visited),
+            PatrolResult::NeverExits
+        );
+        assert_eq!(positions_visited.len(), 4);
+    }
+}
@@ -1,9 +1,16 @@
 #![allow(unused)]
 
-use regex::Regex;
+mod disasm;
+mod instruction;
+mod interpreter;
+mod stream;
+
+use instruction::OPERAND_DIGITS;
+use interpreter::{Error, Machine, Word};
 use std::{
     fs::File,
     io::{self, BufRead},
+    ops::RangeInclusive,
     path::Path,
 };
 
@@ -41,22 +48,68 @@ fn main() {
     //println!("Total was: {}", total); // 188192787
 
     // for second star
-    // Match either multiplication operations or do/dont, to disable/enable following
-    // multiplications
-    let regex = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)|do\(\)|don't\(\)").unwrap();
-    let mut vals = Vec::new();
-
-    // Probably easier to just read the whole file as a single string than as lines
-    let input_string = read_file_as_string("input.txt");
-    let mut perform_multiplications = true;
-
-    for needle in regex.captures_iter(&input_string) {
-        if &needle[0] == "do()" || &needle[0] == "don't()" {
-            perform_multiplications = &needle[0] == "do()";
-        } else if perform_multiplications {
-            vals.push(needle[1].parse::<u32>().unwrap() * needle[2].parse::<u32>().unwrap())
+    // Tokenize into mul/do/don't instructions, and let the machine disable/enable
+    // following multiplications
+
+    // Operands may have 1-3 digits unless `--digits MAX` or `--digits MIN-MAX` says otherwise
+    let args: Vec<String> = std::env::args().collect();
+    let option = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|i| match args.get(i + 1) {
+                Some(value) => value.clone(),
+                None => {
+                    eprintln!("Usage error: {} needs a value", name);
+                    std::process::exit(2)
+                }
+            })
+    };
+    let operand_digits = match option("--digits") {
+        Some(digits) => match digits.split_once('-') {
+            Some((min, max)) => min.parse().unwrap()..=max.parse().unwrap(),
+            None => 1..=digits.parse().unwrap(),
+        },
+        None => OPERAND_DIGITS,
+    };
+
+    // `--disasm` lists every instruction found, and what looked like one but wasn't
+    if args.iter().any(|a| a == "--disasm") {
+        let input_string = read_file_as_string("input.txt");
+        let lines = disasm::disassemble(&input_string, &operand_digits);
+        print!("{}", disasm::render(&input_string, &lines));
+        return;
+    }
+
+    // Sum in 32, 64 or 128 bits (`--width`), overflowing gives an error instead of wrapping
+    let stream = args.iter().any(|a| a == "--stream");
+    let total = match option("--width").as_deref().unwrap_or("64") {
+        "32" => total::<u32>(stream, &operand_digits),
+        "64" => total::<u64>(stream, &operand_digits),
+        "128" => total::<u128>(stream, &operand_digits),
+        width => {
+            eprintln!("Usage error: --width must be 32, 64 or 128, not {}", width);
+            std::process::exit(2)
+        }
+    };
+    match total {
+        Ok(total) => println!("Total is: {}", total),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1)
         }
     }
-    let total: u32 = vals.iter().sum();
-    println!("Total is: {}", total);
+}
+
+// Reading line by line would cut instructions spanning lines, so either read the whole
+// file as a single string, or stream it in chunks for inputs that don't fit in memory
+fn total<T: Word>(stream: bool, operand_digits: &RangeInclusive<usize>) -> Result<String, Error> {
+    let total = if stream {
+        let file = File::open("input.txt")?;
+        Machine::<T>::new().run_stream(file, operand_digits)?
+    } else {
+        let input_string = read_file_as_string("input.txt");
+        let instructions = instruction::tokenize(&input_string, operand_digits);
+        Machine::<T>::new().run(&instructions)?
+    };
+    Ok(total.to_string())
 }
@@ -0,0 +1,115 @@
+//! Visited states of grid walks, shared by the day-six solutions.
+
+/// Number of 4-bit masks in a word.
+const CELLS_PER_WORD: usize = 16;
+
+/// The directions a walker was facing on each cell of a grid, as a dense grid of 4-bit masks.
+/// Cells are indices into the grid, such as `row * width + col`, and directions are indices
+/// from 0 to 3, so any grid simulation can keep its own position and direction types. Testing
+/// and setting a direction at once is a shift and a mask, and clearing only touches the words
+/// that were set, so one grid can be reused for every walk on a large map.
+#[derive(Debug, Clone)]
+pub struct DirectionMasks {
+    words: Vec<u64>,
+    touched: Vec<usize>,
+}
+
+impl DirectionMasks {
+    pub fn new(cells: usize) -> DirectionMasks {
+        DirectionMasks {
+            words: vec![0; cells.div_ceil(CELLS_PER_WORD)],
+            touched: Vec::new(),
+        }
+    }
+
+    /// Adds the direction to the cell, returning whether it was not there before.
+    pub fn insert(&mut self, cell: usize, direction: usize) -> bool {
+        debug_assert!(
+            direction < 4,
+            "Direction index {} is not below 4",
+            direction
+        );
+        let w = cell / CELLS_PER_WORD;
+        let bit = 1 << (cell % CELLS_PER_WORD * 4 + direction);
+        let word = &mut self.words[w];
+        if *word == 0 {
+            self.touched.push(w);
+        }
+        let new = *word & bit == 0;
+        *word |= bit;
+        new
+    }
+
+    /// All directions added to the cell, bit `d` set for direction `d`.
+    pub fn mask(&self, cell: usize) -> u8 {
+        let shift = cell % CELLS_PER_WORD * 4;
+        (self.words[cell / CELLS_PER_WORD] >> shift & 0xf) as u8
+    }
+
+    /// Adds every direction added to `other`, which must have as many cells.
+    pub fn union_with(&mut self, other: &DirectionMasks) {
+        for &w in &other.touched {
+            if self.words[w] == 0 && other.words[w] != 0 {
+                self.touched.push(w);
+            }
+            self.words[w] |= other.words[w];
+        }
+    }
+
+    /// Number of cells with any direction.
+    pub fn len(&self) -> usize {
+        // Folds every mask into its lowest bit
+        const LOWEST_BITS: u64 = 0x1111_1111_1111_1111;
+        self.touched
+            .iter()
+            .map(|&w| self.words[w])
+            .map(|w| ((w | w >> 1 | w >> 2 | w >> 3) & LOWEST_BITS).count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.touched.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        for w in self.touched.drain(..) {
+            self.words[w] = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: usize = 0;
+    const RIGHT: usize = 1;
+    const DOWN: usize = 2;
+    const LEFT: usize = 3;
+
+    #[test]
+    fn test_masks() {
+        let mut masks = DirectionMasks::new(40);
+        assert!(masks.is_empty());
+        assert!(masks.insert(17, DOWN));
+        assert!(!masks.insert(17, DOWN));
+        assert!(masks.insert(17, UP));
+        assert!(masks.insert(39, LEFT));
+        assert_eq!((masks.mask(16), masks.mask(18)), (0, 0));
+        assert_eq!(masks.mask(17), 0b0101);
+        assert_eq!(masks.mask(39), 0b1000);
+        assert_eq!(masks.len(), 2);
+
+        let mut other = DirectionMasks::new(40);
+        other.insert(0, RIGHT);
+        other.insert(17, RIGHT);
+        masks.union_with(&other);
+        assert_eq!((masks.mask(0), masks.mask(17)), (0b0010, 0b0111));
+        assert_eq!(masks.len(), 3);
+
+        masks.clear();
+        assert!(masks.is_empty());
+        assert!((0..40).all(|cell| masks.mask(cell) == 0));
+        assert!(masks.insert(17, DOWN));
+    }
+}
@@ -0,0 +1,257 @@
+//! Ordering rules between pages, shared by the day-five solutions. Pages of any token type are
+//! interned to dense ids and each page keeps a bitset of the pages that have to come after it.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+/// What the rules engine needs of a page identifier, such as a number or a string.
+pub trait Page: Eq + Hash + Clone + fmt::Debug + fmt::Display {}
+
+impl<T: Eq + Hash + Clone + fmt::Debug + fmt::Display> Page for T {}
+
+/// Hands out dense indices for tokens, in order of first appearance.
+#[derive(Debug, Clone)]
+pub struct Interner<T> {
+    ids: HashMap<T, usize>,
+    tokens: Vec<T>,
+}
+
+impl<T: Eq + Hash + Clone> Interner<T> {
+    pub fn new() -> Interner<T> {
+        Interner {
+            ids: HashMap::new(),
+            tokens: Vec::new(),
+        }
+    }
+
+    pub fn intern(&mut self, token: T) -> usize {
+        if let Some(&id) = self.ids.get(&token) {
+            return id;
+        }
+        self.tokens.push(token.clone());
+        self.ids.insert(token, self.tokens.len() - 1);
+        self.tokens.len() - 1
+    }
+
+    pub fn get(&self, token: &T) -> Option<usize> {
+        self.ids.get(token).copied()
+    }
+
+    pub fn token(&self, id: usize) -> &T {
+        &self.tokens[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+impl<T: Eq + Hash + Clone> Default for Interner<T> {
+    fn default() -> Interner<T> {
+        Interner::new()
+    }
+}
+
+/// A set of small integers, one bit each. It grows as bits are set and reads bits past its end
+/// as unset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new() -> BitSet {
+        BitSet::default()
+    }
+
+    /// Sets bit `i`, returning whether it was unset before.
+    pub fn insert(&mut self, i: usize) -> bool {
+        if i / 64 >= self.words.len() {
+            self.words.resize(i / 64 + 1, 0);
+        }
+        let was_set = self.contains(i);
+        self.words[i / 64] |= 1 << (i % 64);
+        !was_set
+    }
+
+    /// Clears bit `i`, returning whether it was set before.
+    pub fn remove(&mut self, i: usize) -> bool {
+        let was_set = self.contains(i);
+        if was_set {
+            self.words[i / 64] &= !(1 << (i % 64));
+        }
+        was_set
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.words
+            .get(i / 64)
+            .is_some_and(|word| word & (1 << (i % 64)) != 0)
+    }
+
+    /// The set bits in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| w * 64 + bit)
+        })
+    }
+}
+
+/// Ordering rules between pages of any token type. Pages are interned to dense ids and the
+/// rules are kept as an adjacency matrix with one bitset row per page, so checking a rule is a
+/// single bit lookup.
+#[derive(Debug, Clone)]
+pub struct Rules<T> {
+    pages: Interner<T>,
+    /// `after[a]` holds every page that has to come after page `a`.
+    after: Vec<BitSet>,
+    /// How many more times than once each rule was added.
+    repeats: HashMap<(usize, usize), usize>,
+}
+
+impl<T: Eq + Hash + Clone> Rules<T> {
+    pub fn new() -> Rules<T> {
+        Rules {
+            pages: Interner::new(),
+            after: Vec::new(),
+            repeats: HashMap::new(),
+        }
+    }
+
+    pub fn intern(&mut self, page: T) -> usize {
+        let id = self.pages.intern(page);
+        if id == self.after.len() {
+            self.after.push(BitSet::new());
+        }
+        id
+    }
+
+    pub fn id(&self, page: &T) -> Option<usize> {
+        self.pages.get(page)
+    }
+
+    pub fn page(&self, id: usize) -> &T {
+        self.pages.token(id)
+    }
+
+    /// Number of interned pages.
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Adds the rule `before|after`, returning whether it is new.
+    pub fn add(&mut self, before: T, after: T) -> bool {
+        let (a, b) = (self.intern(before), self.intern(after));
+        let new = self.after[a].insert(b);
+        if !new {
+            *self.repeats.entry((a, b)).or_insert(0) += 1;
+        }
+        new
+    }
+
+    /// Removes the rule `before|after` however many times it was added, returning whether it
+    /// was there.
+    pub fn remove(&mut self, before: &T, after: &T) -> bool {
+        let (Some(a), Some(b)) = (self.id(before), self.id(after)) else {
+            return false;
+        };
+        self.repeats.remove(&(a, b));
+        self.after[a].remove(b)
+    }
+
+    /// Whether page `a` has to come before page `b`. Ids past the interned pages are allowed
+    /// and take part in no rule.
+    pub fn precedes(&self, a: usize, b: usize) -> bool {
+        self.after.get(a).is_some_and(|after| after.contains(b))
+    }
+
+    /// The pages that have to come after page `a`.
+    pub fn successors(&self, a: usize) -> &BitSet {
+        &self.after[a]
+    }
+
+    /// How many times the rule `a|b` was added.
+    pub fn times_added(&self, a: usize, b: usize) -> usize {
+        if !self.precedes(a, b) {
+            return 0;
+        }
+        1 + self.repeats.get(&(a, b)).copied().unwrap_or(0)
+    }
+
+    /// Every rule as a pair of ids, ordered by the first and then the second id.
+    pub fn pairs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.after
+            .iter()
+            .enumerate()
+            .flat_map(|(a, after)| after.iter().map(move |b| (a, b)))
+    }
+
+    /// The ids of the pages of an instruction. Pages that were never interned get ids past
+    /// the interned ones, so that no rule applies to them.
+    pub fn ids(&self, instruction: &[T]) -> Vec<usize> {
+        let mut unknown = self.len();
+        instruction
+            .iter()
+            .map(|page| {
+                self.id(page).unwrap_or_else(|| {
+                    unknown += 1;
+                    unknown - 1
+                })
+            })
+            .collect()
+    }
+}
+
+impl<T: Eq + Hash + Clone> Default for Rules<T> {
+    fn default() -> Rules<T> {
+        Rules::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitset() {
+        let mut set = BitSet::new();
+        assert!(set.insert(3));
+        assert!(set.insert(130));
+        assert!(!set.insert(3));
+        assert!(set.contains(130) && !set.contains(129) && !set.contains(1000));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![3, 130]);
+        assert!(set.remove(3) && !set.remove(3));
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![130]);
+    }
+
+    #[test]
+    fn test_rules_with_string_pages() {
+        let mut rules = Rules::new();
+        assert!(rules.add("intro", "body"));
+        assert!(rules.add("body", "outro"));
+        assert!(!rules.add("intro", "body"));
+
+        let ids = rules.ids(&["outro", "appendix", "intro", "index"]);
+        assert_eq!(ids, vec![2, 3, 0, 4]);
+        assert!(rules.precedes(ids[2], rules.id(&"body").unwrap()));
+        assert!(!rules.precedes(ids[0], ids[2]));
+        assert!(!rules.precedes(ids[1], ids[3]));
+        assert_eq!(rules.times_added(0, 1), 2);
+        assert_eq!(rules.pairs().collect::<Vec<_>>(), vec![(0, 1), (1, 2)]);
+
+        assert!(rules.remove(&"intro", &"body"));
+        assert_eq!(rules.times_added(0, 1), 0);
+        assert!(!rules.remove(&"intro", &"missing"));
+    }
+}
@@ -0,0 +1,313 @@
+use crate::find_cycle;
+use page_rules::{BitSet, Page, Rules};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lint<T> {
+    /// The rule `before|after` is listed `count` times.
+    Duplicate { before: T, after: T, count: usize },
+    /// Both `a|b` and `b|a` are listed, so no instruction with both pages can be valid.
+    Contradiction { a: T, b: T },
+    /// The rules between `pages` form cycles, `cycle` being one of them. Instructions with only
+    /// some of these pages may still be ordered, instructions with all of them cannot. A single
+    /// page is a rule `a|a`.
+    Cycle { pages: Vec<T>, cycle: Vec<T> },
+    /// `page` appears in the listed instructions but in no rule, so it can go anywhere.
+    Unconstrained { page: T, instructions: Vec<usize> },
+}
+
+impl<T: Page> fmt::Display for Lint<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lint::Duplicate {
+                before,
+                after,
+                count,
+            } => write!(f, "Rule {}|{} is listed {} times", before, after, count),
+            Lint::Contradiction { a, b } => {
+                write!(f, "Rules {}|{} and {}|{} contradict each other", a, b, b, a)
+            }
+            Lint::Cycle { pages, cycle } if pages.len() == 1 => {
+                write!(f, "Rule {}|{} puts a page after itself", cycle[0], cycle[0])
+            }
+            Lint::Cycle { pages, cycle } => {
+                let cycle: Vec<String> = cycle
+                    .iter()
+                    .chain(cycle.first())
+                    .map(|p| p.to_string())
+                    .collect();
+                write!(
+                    f,
+                    "Rules between {} pages form cycles, such as {}",
+                    pages.len(),
+                    cycle.join(" -> ")
+                )
+            }
+            Lint::Unconstrained { page, instructions } => write!(
+                f,
+                "Page {} is in {} instructions but in no rule",
+                page,
+                instructions.len()
+            ),
+        }
+    }
+}
+
+/// Strongly connected components of the rule graph that hold a cycle, that is with more than
+/// one page or with a page that has to come after itself, found with Tarjan's algorithm. Pages
+/// are visited with a stack of their own rather than by recursion, as chains of rules can be
+/// longer than the call stack allows.
+fn cyclic_components<T: Page>(rules: &Rules<T>) -> Vec<Vec<usize>> {
+    struct Tarjan<'a, T> {
+        rules: &'a Rules<T>,
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        visited: usize,
+        stack: Vec<usize>,
+        on_stack: BitSet,
+        /// Pages being visited, innermost last, each with the successors still to look at.
+        calls: Vec<(usize, Vec<usize>)>,
+        components: Vec<Vec<usize>>,
+    }
+
+    impl<T: Page> Tarjan<'_, T> {
+        fn enter(&mut self, page: usize) {
+            self.index[page] = Some(self.visited);
+            self.low[page] = self.visited;
+            self.visited += 1;
+            self.stack.push(page);
+            self.on_stack.insert(page);
+            let successors = self.rules.successors(page).iter().collect();
+            self.calls.push((page, successors));
+        }
+
+        fn visit(&mut self, root: usize) {
+            self.enter(root);
+            while let Some((page, successors)) = self.calls.last_mut() {
+                let page = *page;
+                if let Some(next) = successors.pop() {
+                    match self.index[next] {
+                        None => self.enter(next),
+                        Some(index) if self.on_stack.contains(next) => {
+                            self.low[page] = self.low[page].min(index);
+                        }
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+
+                self.calls.pop();
+                if let Some(&(caller, _)) = self.calls.last() {
+                    self.low[caller] = self.low[caller].min(self.low[page]);
+                }
+                if Some(self.low[page]) == self.index[page] {
+                    let mut component = Vec::new();
+                    while let Some(p) = self.stack.pop() {
+                        self.on_stack.remove(p);
+                        component.push(p);
+                        if p == page {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 || self.rules.precedes(page, page) {
+                        component.sort();
+                        self.components.push(component);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        rules,
+        index: vec![None; rules.len()],
+        low: vec![0; rules.len()],
+        visited: 0,
+        stack: Vec::new(),
+        on_stack: BitSet::new(),
+        calls: Vec::new(),
+        components: Vec::new(),
+    };
+    for page in 0..rules.len() {
+        if tarjan.index[page].is_none() {
+            tarjan.visit(page);
+        }
+    }
+    tarjan.components
+}
+
+/// Checks the rules and instructions for problems that make the puzzle ill-posed or that hint
+/// at a mistake in the input.
+pub fn lint<T: Page + Ord>(rules: &Rules<T>, instructions: &[Vec<T>]) -> Vec<Lint<T>> {
+    let page = |id: usize| rules.page(id).clone();
+    let pairs: BTreeMap<(T, T), (usize, usize)> = rules
+        .pairs()
+        .map(|(a, b)| ((page(a), page(b)), (a, b)))
+        .collect();
+    let mut lints = Vec::new();
+
+    for ((before, after), &(a, b)) in &pairs {
+        let count = rules.times_added(a, b);
+        if count > 1 {
+            lints.push(Lint::Duplicate {
+                before: before.clone(),
+                after: after.clone(),
+                count,
+            });
+        }
+    }
+    for ((before, after), &(a, b)) in &pairs {
+        if before < after && rules.precedes(b, a) {
+            lints.push(Lint::Contradiction {
+                a: before.clone(),
+                b: after.clone(),
+            });
+        }
+    }
+    // Components of two pages are contradictions, which are already reported, unless a page
+    // also has to come after itself
+    let mut components: Vec<(Vec<T>, Vec<usize>)> = cyclic_components(rules)
+        .into_iter()
+        .filter(|ids| ids.len() != 2 || ids.iter().any(|&id| rules.precedes(id, id)))
+        .map(|ids| {
+            let mut pages: Vec<T> = ids.iter().map(|&id| page(id)).collect();
+            pages.sort();
+            (pages, ids)
+        })
+        .collect();
+    components.sort();
+    for (pages, ids) in components {
+        let cycle = find_cycle(&ids, rules).into_iter().map(page).collect();
+        lints.push(Lint::Cycle { pages, cycle });
+    }
+
+    let mut ruled = BitSet::new();
+    for (a, b) in rules.pairs() {
+        ruled.insert(a);
+        ruled.insert(b);
+    }
+    let mut unconstrained: BTreeMap<T, Vec<usize>> = BTreeMap::new();
+    for (i, instr) in instructions.iter().enumerate() {
+        for (p, id) in instr.iter().zip(rules.ids(instr)) {
+            if !ruled.contains(id) {
+                unconstrained.entry(p.clone()).or_default().push(i);
+            }
+        }
+    }
+    for (page, instructions) in unconstrained {
+        lints.push(Lint::Unconstrained { page, instructions });
+    }
+    lints
+}
+
+/// The rules as a Graphviz digraph with an edge from each page to the pages that have to come
+/// after it. Contradicting rules are drawn in red.
+pub fn to_dot<T: Page>(rules: &Rules<T>) -> String {
+    let mut out = String::from("digraph rules {\n");
+    for (a, b) in rules.pairs() {
+        let (before, after) = (rules.page(a).to_string(), rules.page(b).to_string());
+        write!(out, "    {:?} -> {:?}", before, after).unwrap();
+        if rules.precedes(b, a) {
+            out.push_str(" [color=red]");
+        }
+        out.push_str(";\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules_from_pairs(pairs: &[(u32, u32)]) -> Rules<u32> {
+        let mut rules = Rules::new();
+        for &(before, after) in pairs {
+            rules.add(before, after);
+        }
+        rules
+    }
+
+    #[test]
+    fn test_lint() {
+        let (rules, instructions) = crate::read_rules_and_instructions::<u32>("test.txt").unwrap();
+        assert_eq!(lint(&rules, &instructions), vec![]);
+
+        let rules = rules_from_pairs(&[(1, 2), (1, 2), (2, 3), (3, 1), (4, 5), (5, 4)]);
+        let instructions = vec![vec![1, 2, 6], vec![6, 7]];
+        assert_eq!(
+            lint(&rules, &instructions),
+            vec![
+                Lint::Duplicate {
+                    before: 1,
+                    after: 2,
+                    count: 2
+                },
+                Lint::Contradiction { a: 4, b: 5 },
+                Lint::Cycle {
+                    pages: vec![1, 2, 3],
+                    cycle: vec![2, 3, 1]
+                },
+                Lint::Unconstrained {
+                    page: 6,
+                    instructions: vec![0, 1]
+                },
+                Lint::Unconstrained {
+                    page: 7,
+                    instructions: vec![1]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_self_rules() {
+        let rules = rules_from_pairs(&[(3, 3), (1, 2), (2, 1), (2, 2), (4, 5), (5, 6), (6, 4)]);
+        let cycles: Vec<Lint<u32>> = lint(&rules, &[])
+            .into_iter()
+            .filter(|l| matches!(l, Lint::Cycle { .. }))
+            .collect();
+        assert_eq!(
+            cycles,
+            vec![
+                Lint::Cycle {
+                    pages: vec![1, 2],
+                    cycle: vec![2, 1]
+                },
+                Lint::Cycle {
+                    pages: vec![3],
+                    cycle: vec![3]
+                },
+                Lint::Cycle {
+                    pages: vec![4, 5, 6],
+                    cycle: vec![5, 6, 4]
+                },
+            ]
+        );
+        assert_eq!(cycles[1].to_string(), "Rule 3|3 puts a page after itself");
+    }
+
+    #[test]
+    fn test_long_chain() {
+        // Deep enough to overflow the stack if pages were visited recursively
+        let chain: Vec<(u32, u32)> = (0..10_000).map(|p| (p, p + 1)).collect();
+        assert_eq!(lint(&rules_from_pairs(&chain), &[]), vec![]);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let rules = rules_from_pairs(&[(1, 2), (2, 1), (1, 3)]);
+        assert_eq!(
+            to_dot(&rules),
+            concat!(
+                "digraph rules {\n",
+                "    \"1\" -> \"2\" [color=red];\n",
+                "    \"1\" -> \"3\";\n",
+                "    \"2\" -> \"1\" [color=red];\n",
+                "}\n"
+            )
+        );
+    }
+}
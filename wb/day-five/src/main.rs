@@ -1,23 +1,63 @@
-use std::collections::HashMap;
-use std::fs;
+mod lint;
+mod report;
+mod whatif;
+
+use page_rules::{Page, Rules};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use std::{env, fs};
 
 #[cfg(feature = "dev")]
 const FILE_NAME: &str = "test.txt";
 #[cfg(feature = "prod")]
 const FILE_NAME: &str = "input.txt";
 
-fn parse_numbers(line: &str, separator: char) -> Vec<u32> {
-    line.split(separator)
-        .map(|p| p.parse::<u32>().expect("Invalid number"))
-        .collect()
+/// Why a line of the input could not be read.
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    /// A page that is not a number.
+    Page(String),
+    /// A rule that is not two pages separated by `|`.
+    Rule(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::Page(page) => write!(f, "Invalid page: {}", page),
+            ParseError::Rule(rule) => {
+                write!(f, "Invalid rule {}, expected two pages like 47|53", rule)
+            }
+        }
+    }
+}
+
+fn parse_page<T: FromStr>(page: &str) -> Result<T, ParseError> {
+    page.parse::<T>()
+        .map_err(|_| ParseError::Page(page.to_string()))
 }
 
-fn read_rules_and_instructions(path: &str) -> (HashMap<u32, Vec<u32>>, Vec<Vec<u32>>) {
+fn parse_pages<T: FromStr>(line: &str) -> Result<Vec<T>, ParseError> {
+    line.split(',').map(parse_page).collect()
+}
+
+/// Reads a rule `before|after`.
+fn parse_rule<T: FromStr>(line: &str) -> Result<(T, T), ParseError> {
+    match line.split_once('|') {
+        Some((before, after)) if !after.contains('|') => {
+            Ok((parse_page(before)?, parse_page(after)?))
+        }
+        _ => Err(ParseError::Rule(line.to_string())),
+    }
+}
+
+fn read_rules_and_instructions<T: Page + FromStr>(
+    path: &str,
+) -> Result<(Rules<T>, Vec<Vec<T>>), ParseError> {
     let file = fs::read_to_string(path).expect("Could not read file");
-    let mut order_rules = HashMap::new();
+    let mut order_rules = Rules::new();
     let mut instructions = Vec::new();
     let mut rules_done = false;
-    let separator = |done: bool| if done { ',' } else { '|' };
 
     for line in file.lines() {
         log::debug!("Processing line: {}", line);
@@ -28,71 +68,202 @@ fn read_rules_and_instructions(path: &str) -> (HashMap<u32, Vec<u32>>, Vec<Vec<u
             continue;
         }
 
-        let numbers = parse_numbers(line, separator(rules_done));
         if !rules_done {
-            order_rules
-                .entry(numbers[1])
-                .or_insert_with(Vec::new)
-                .push(numbers[0]);
+            let (before, after) = parse_rule(line)?;
+            order_rules.add(before, after);
         } else {
-            instructions.push(numbers);
+            instructions.push(parse_pages(line)?);
         }
     }
-    log::info!("Parsed {} rules and {} instructions", order_rules.len(), instructions.len());
-    (order_rules, instructions)
+    log::info!(
+        "Parsed {} rules between {} pages and {} instructions",
+        order_rules.pairs().count(),
+        order_rules.len(),
+        instructions.len()
+    );
+    Ok((order_rules, instructions))
 }
 
-fn validate_instruction(
-    instruction: &[u32],
-    rules: &HashMap<u32, Vec<u32>>,
-) -> (bool, usize, usize) {
+fn validate_instruction<T: Page>(instruction: &[T], rules: &Rules<T>) -> (bool, usize, usize) {
     log::debug!("Validating instruction: {:?}", instruction);
-    let indices: HashMap<u32, usize> = instruction
-        .iter()
-        .enumerate()
-        .map(|(i, &step)| (step, i))
-        .collect();
+    let ids = rules.ids(instruction);
 
-    for (i, &step) in instruction.iter().enumerate() {
-        if let Some(pre_steps) = rules.get(&step) {
-            if let Some(violating_step) = pre_steps
-                .iter()
-                .find(|&&ps| indices.get(&ps).map_or(false, |&index| index > i))
-            {
-                log::debug!(
-                    "Validation failed: step {} requires step {} to occur before index {}",
-                    step,
-                    violating_step,
-                    i
-                );
-                return (false, i, *indices.get(violating_step).unwrap());
-            }
+    for (i, step) in instruction.iter().enumerate() {
+        if let Some(j) = (i + 1..ids.len()).find(|&j| rules.precedes(ids[j], ids[i])) {
+            log::debug!(
+                "Validation failed: step {} requires step {} to occur before index {}",
+                step,
+                instruction[j],
+                i
+            );
+            return (false, i, j);
         }
     }
     log::debug!("Validation successful for instruction: {:?}", instruction);
     (true, 0, 0)
 }
 
+/// A set of rules restricted to the pages of one instruction that cannot all be satisfied.
+/// `pages` lists the cycle in order, each page required before the next and the last one
+/// required before the first.
+#[derive(Debug, PartialEq)]
+struct CycleError<T> {
+    pages: Vec<T>,
+}
 
-fn correct_order(instruction: &[u32], rules: &HashMap<u32, Vec<u32>>) -> Vec<u32> {
-    let mut instr_clone = instruction.to_vec();
-    while let (false, fst, snd) = validate_instruction(&instr_clone, rules) {
-        log::debug!(
-            "Before swap: {:?}, swapping indices {} and {}",
-            instr_clone, fst, snd
-        );
+impl<T: Page> std::fmt::Display for CycleError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let cycle: Vec<String> = self
+            .pages
+            .iter()
+            .chain(self.pages.first())
+            .map(|p| p.to_string())
+            .collect();
+        write!(
+            f,
+            "Contradictory rules form a cycle: {}",
+            cycle.join(" -> ")
+        )
+    }
+}
 
-        instr_clone.swap(fst, snd);
+/// Finds a cycle among the `remaining` page ids, which all still have a predecessor among
+/// them, by walking backwards along the rules until a page repeats.
+fn find_cycle<T: Page>(remaining: &[usize], rules: &Rules<T>) -> Vec<usize> {
+    let mut path = vec![remaining[0]];
+    loop {
+        let current = *path.last().unwrap();
+        let previous = *remaining
+            .iter()
+            .find(|&&p| rules.precedes(p, current))
+            .unwrap();
+        if let Some(start) = path.iter().position(|&p| p == previous) {
+            let mut cycle = path.split_off(start);
+            cycle.reverse();
+            return cycle;
+        }
+        path.push(previous);
+    }
+}
+
+/// Orders the instruction by a topological sort of the rules between its steps. When several
+/// steps are free to go next, the one that came first in the instruction is picked.
+fn correct_order<T: Page>(instruction: &[T], rules: &Rules<T>) -> Result<Vec<T>, CycleError<T>> {
+    let ids = rules.ids(instruction);
+    let mut n_before: Vec<usize> = ids
+        .iter()
+        .map(|&step| ids.iter().filter(|&&ps| rules.precedes(ps, step)).count())
+        .collect();
+    let mut ready: BTreeSet<usize> = (0..ids.len()).filter(|&i| n_before[i] == 0).collect();
+
+    let mut ordered = Vec::with_capacity(ids.len());
+    while let Some(i) = ready.pop_first() {
+        ordered.push(i);
+        for (j, &later_step) in ids.iter().enumerate() {
+            if rules.precedes(ids[i], later_step) {
+                n_before[j] -= 1;
+                if n_before[j] == 0 {
+                    ready.insert(j);
+                }
+            }
+        }
+    }
 
-        log::debug!("After swap: {:?}", instr_clone);
+    if ordered.len() < ids.len() {
+        let remaining: Vec<usize> = (0..ids.len())
+            .filter(|i| !ordered.contains(i))
+            .map(|i| ids[i])
+            .collect();
+        let pages = find_cycle(&remaining, rules)
+            .into_iter()
+            .map(|id| rules.page(id).clone())
+            .collect();
+        return Err(CycleError { pages });
     }
-    instr_clone
+    let ordered: Vec<T> = ordered
+        .into_iter()
+        .map(|i| instruction[i].clone())
+        .collect();
+    log::debug!("Corrected {:?} to {:?}", instruction, ordered);
+    Ok(ordered)
 }
 
 fn main() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let (rules, instructions) = read_rules_and_instructions(FILE_NAME);
+    let (rules, instructions) = match read_rules_and_instructions::<u32>(FILE_NAME) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            log::error!("{}", e);
+            return;
+        }
+    };
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let option = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|i| match args.get(i + 1) {
+                Some(value) => value.as_str(),
+                None => {
+                    eprintln!("Usage error: {} needs a value", name);
+                    std::process::exit(2)
+                }
+            })
+    };
+    if let Some(path) = option("--dot") {
+        fs::write(path, lint::to_dot(&rules)).expect("Could not write file");
+        log::info!("Wrote rule graph to {}", path);
+    }
+    if args.iter().any(|a| a == "--lint") {
+        for l in lint::lint(&rules, &instructions) {
+            println!("{}", l);
+        }
+        return;
+    }
+    if option("--add-rule").is_some() || option("--remove-rule").is_some() {
+        let mut session = whatif::Session::new(rules, instructions);
+        let sums = |s: &whatif::Session<u32>| {
+            let corrected = s
+                .corrected_sum()
+                .map_or("cycle".to_string(), |c| c.to_string());
+            format!("first star {}, second star {}", s.valid_sum(), corrected)
+        };
+        println!("Before: {}", sums(&session));
+        let parse = |rule: &str| {
+            parse_rule::<u32>(rule).unwrap_or_else(|e| {
+                log::error!("{}", e);
+                std::process::exit(1)
+            })
+        };
+        if let Some(rule) = option("--remove-rule") {
+            let (before, after) = parse(rule);
+            println!("Removing rule {}", rule);
+            for change in session.remove_rule(&before, &after) {
+                println!("    {}", change);
+            }
+        }
+        if let Some(rule) = option("--add-rule") {
+            let (before, after) = parse(rule);
+            println!("Adding rule {}", rule);
+            for change in session.add_rule(before, after) {
+                println!("    {}", change);
+            }
+        }
+        println!("After: {}", sums(&session));
+        return;
+    }
+    if args.iter().any(|a| a == "--report") {
+        let reports = report::report_instructions(&instructions, &rules);
+        if args.iter().any(|a| a == "--json") {
+            println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+        } else {
+            for r in &reports {
+                println!("{}", r);
+            }
+        }
+        return;
+    }
 
     let validation_results: Vec<_> = instructions
         .iter()
@@ -107,11 +278,18 @@ fn main() {
 
     log::info!("First star result: {}", first_star_result);
 
-    let second_star_result: u32 = instructions
+    let second_star_result: Result<u32, CycleError<u32>> = instructions
         .iter()
         .filter(|instr| !validate_instruction(instr, &rules).0)
-        .map(|instr| correct_order(instr, &rules)[instr.len() / 2])
+        .map(|instr| correct_order(instr, &rules).map(|ordered| ordered[instr.len() / 2]))
         .sum();
+    let second_star_result = match second_star_result {
+        Ok(result) => result,
+        Err(e) => {
+            log::error!("{}", e);
+            return;
+        }
+    };
 
     log::info!("Second star result: {}", second_star_result);
 }
@@ -122,7 +300,7 @@ mod tests {
 
     #[test]
     fn test_validate_instruction() {
-        let (rules, instructions) = read_rules_and_instructions("test.txt");
+        let (rules, instructions) = read_rules_and_instructions::<u32>("test.txt").unwrap();
         let expected = vec![true, true, true, false, false, false];
         let mut output: Vec<bool> = Vec::new();
         for instr in instructions {
@@ -130,4 +308,60 @@ mod tests {
         }
         assert_eq!(expected, output);
     }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(parse_rule::<u32>("47|53"), Ok((47, 53)));
+        let invalid = |rule: &str| Err(ParseError::Rule(rule.to_string()));
+        assert_eq!(parse_rule::<u32>("1|2|3"), invalid("1|2|3"));
+        assert_eq!(parse_rule::<u32>("12"), invalid("12"));
+        assert_eq!(
+            parse_rule::<u32>("1|"),
+            Err(ParseError::Page(String::new()))
+        );
+    }
+
+    #[test]
+    fn test_correct_order() {
+        let (rules, instructions) = read_rules_and_instructions::<u32>("test.txt").unwrap();
+        let corrected: Vec<Vec<u32>> = instructions[3..]
+            .iter()
+            .map(|instr| correct_order(instr, &rules).unwrap())
+            .collect();
+        let expected = vec![
+            vec![97, 75, 47, 61, 53],
+            vec![61, 29, 13],
+            vec![97, 75, 47, 29, 13],
+        ];
+        assert_eq!(corrected, expected);
+    }
+
+    #[test]
+    fn test_correct_order_reports_cycle() {
+        let mut rules = Rules::new();
+        rules.add(1, 2);
+        rules.add(2, 3);
+        rules.add(3, 1);
+        rules.add(1, 4);
+        let error = correct_order(&[4, 3, 2, 1], &rules).unwrap_err();
+        assert_eq!(error.pages.len(), 3);
+        assert_eq!(
+            error.to_string(),
+            "Contradictory rules form a cycle: 2 -> 3 -> 1 -> 2"
+        );
+    }
+
+    #[test]
+    fn test_correct_order_with_string_pages() {
+        let mut rules = Rules::new();
+        rules.add("title", "abstract");
+        rules.add("abstract", "body");
+        rules.add("title", "body");
+        let instruction = ["body", "index", "title", "abstract"];
+        assert!(!validate_instruction(&instruction, &rules).0);
+        assert_eq!(
+            correct_order(&instruction, &rules).unwrap(),
+            vec!["index", "title", "abstract", "body"]
+        );
+    }
 }
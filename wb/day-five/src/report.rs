@@ -0,0 +1,279 @@
+use crate::{correct_order, CycleError};
+use page_rules::{Page, Rules};
+use serde::Serialize;
+use std::fmt;
+
+/// A rule `before|after` broken by an instruction, where `before` occurs at `before_index`,
+/// after `after` at `after_index`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation<T> {
+    pub before: T,
+    pub after: T,
+    pub before_index: usize,
+    pub after_index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum Fix<T> {
+    Valid,
+    /// The instruction can be put into `order`, either by swapping pairs of steps or by taking
+    /// steps out and inserting them elsewhere. Both counts are for reaching this one order, when
+    /// the rules allow several another one may be fewer steps away.
+    Reorder {
+        order: Vec<T>,
+        swaps_to_order: usize,
+        moves_to_order: usize,
+    },
+    /// The rules between the steps contradict each other.
+    Cycle {
+        cycle: Vec<T>,
+    },
+    /// The instruction has `step` more than once, so it is not clear which copy goes where and
+    /// the swaps or moves are not counted.
+    RepeatedStep {
+        step: T,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstructionReport<T> {
+    pub instruction: usize,
+    pub steps: Vec<T>,
+    pub violations: Vec<Violation<T>>,
+    #[serde(flatten)]
+    pub fix: Fix<T>,
+}
+
+/// Every pair of steps in the instruction that breaks a rule, ordered by the position of the
+/// later step of the rule and then of the earlier one.
+pub fn find_violations<T: Page>(instruction: &[T], rules: &Rules<T>) -> Vec<Violation<T>> {
+    let ids = rules.ids(instruction);
+    let mut violations = Vec::new();
+    for i in 0..ids.len() {
+        for j in i + 1..ids.len() {
+            if rules.precedes(ids[j], ids[i]) {
+                violations.push(Violation {
+                    before: instruction[j].clone(),
+                    after: instruction[i].clone(),
+                    before_index: j,
+                    after_index: i,
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// For every step, its index in `order`. Steps have to occur once per instruction.
+fn target_indices<T: Page>(instruction: &[T], order: &[T]) -> Vec<usize> {
+    instruction
+        .iter()
+        .map(|step| order.iter().position(|s| s == step).unwrap())
+        .collect()
+}
+
+/// The fewest swaps of two steps turning the instruction into `order`: every cycle of the
+/// permutation between them takes one swap less than its length.
+fn swaps_to_order(targets: &[usize]) -> usize {
+    let mut seen = vec![false; targets.len()];
+    let mut cycles = 0;
+    for start in 0..targets.len() {
+        if seen[start] {
+            continue;
+        }
+        cycles += 1;
+        let mut i = start;
+        while !seen[i] {
+            seen[i] = true;
+            i = targets[i];
+        }
+    }
+    targets.len() - cycles
+}
+
+/// The fewest steps to take out and insert elsewhere to turn the instruction into `order`.
+/// The steps left in place are a longest increasing run of target indices.
+fn moves_to_order(targets: &[usize]) -> usize {
+    // Smallest possible last target index of an increasing run of each length
+    let mut tails: Vec<usize> = Vec::new();
+    for &t in targets {
+        let at = tails.partition_point(|&tail| tail < t);
+        if at == tails.len() {
+            tails.push(t);
+        } else {
+            tails[at] = t;
+        }
+    }
+    targets.len() - tails.len()
+}
+
+pub fn report_instruction<T: Page>(
+    index: usize,
+    instruction: &[T],
+    rules: &Rules<T>,
+) -> InstructionReport<T> {
+    let violations = find_violations(instruction, rules);
+    let repeated = (1..instruction.len()).find(|&i| instruction[..i].contains(&instruction[i]));
+    let fix = if violations.is_empty() {
+        Fix::Valid
+    } else if let Some(i) = repeated {
+        Fix::RepeatedStep {
+            step: instruction[i].clone(),
+        }
+    } else {
+        match correct_order(instruction, rules) {
+            Ok(order) => {
+                let targets = target_indices(instruction, &order);
+                Fix::Reorder {
+                    swaps_to_order: swaps_to_order(&targets),
+                    moves_to_order: moves_to_order(&targets),
+                    order,
+                }
+            }
+            Err(CycleError { pages }) => Fix::Cycle { cycle: pages },
+        }
+    };
+    InstructionReport {
+        instruction: index,
+        steps: instruction.to_vec(),
+        violations,
+        fix,
+    }
+}
+
+pub fn report_instructions<T: Page>(
+    instructions: &[Vec<T>],
+    rules: &Rules<T>,
+) -> Vec<InstructionReport<T>> {
+    instructions
+        .iter()
+        .enumerate()
+        .map(|(i, instr)| report_instruction(i, instr, rules))
+        .collect()
+}
+
+fn join<T: Page>(steps: &[T]) -> String {
+    steps
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl<T: Page> fmt::Display for Violation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}|{} broken: {} at index {}, {} at index {}",
+            self.before, self.after, self.before, self.before_index, self.after, self.after_index
+        )
+    }
+}
+
+impl<T: Page> fmt::Display for InstructionReport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Instruction {} ({}): ",
+            self.instruction,
+            join(&self.steps)
+        )?;
+        match &self.fix {
+            Fix::Valid => return write!(f, "valid"),
+            Fix::Reorder {
+                order,
+                swaps_to_order,
+                moves_to_order,
+            } => write!(
+                f,
+                "{} violations, fixed to {} by {} swaps or {} moves",
+                self.violations.len(),
+                join(order),
+                swaps_to_order,
+                moves_to_order
+            )?,
+            Fix::Cycle { cycle } => write!(
+                f,
+                "{} violations, cannot be fixed: {}",
+                self.violations.len(),
+                CycleError {
+                    pages: cycle.clone()
+                }
+            )?,
+            Fix::RepeatedStep { step } => write!(
+                f,
+                "{} violations, cannot be reordered: {} occurs more than once",
+                self.violations.len(),
+                step
+            )?,
+        }
+        for violation in &self.violations {
+            write!(f, "\n    {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_instructions() {
+        let (rules, instructions) = crate::read_rules_and_instructions::<u32>("test.txt").unwrap();
+        let reports = report_instructions(&instructions, &rules);
+        let valid: Vec<bool> = reports.iter().map(|r| r.fix == Fix::Valid).collect();
+        assert_eq!(valid, vec![true, true, true, false, false, false]);
+
+        assert_eq!(
+            reports[3].violations,
+            vec![Violation {
+                before: 97,
+                after: 75,
+                before_index: 1,
+                after_index: 0,
+            }]
+        );
+        assert_eq!(
+            reports[5].fix,
+            Fix::Reorder {
+                order: vec![97, 75, 47, 29, 13],
+                swaps_to_order: 2,
+                moves_to_order: 2,
+            }
+        );
+        assert_eq!(reports[5].violations.len(), 4);
+
+        // 3 may go anywhere, so 1,3,2 is valid too and only one swap away, but the counts are
+        // for the order that is printed
+        let mut loose = Rules::new();
+        loose.add(1, 2);
+        let report = report_instruction(0, &[2, 3, 1], &loose);
+        assert_eq!(
+            report.fix,
+            Fix::Reorder {
+                order: vec![3, 1, 2],
+                swaps_to_order: 2,
+                moves_to_order: 1,
+            }
+        );
+
+        // Either copy of 75 could go after 97, so there is no one permutation to count
+        let report = report_instruction(0, &[75, 97, 75], &rules);
+        assert_eq!(report.violations.len(), 1);
+        assert_eq!(report.fix, Fix::RepeatedStep { step: 75 });
+    }
+
+    #[test]
+    fn test_swaps_and_moves_to_order() {
+        // Rotating by one is a single cycle, but only one step has to move
+        let rotated = [1, 2, 3, 4, 0];
+        assert_eq!(swaps_to_order(&rotated), 4);
+        assert_eq!(moves_to_order(&rotated), 1);
+
+        let reversed = [4, 3, 2, 1, 0];
+        assert_eq!(swaps_to_order(&reversed), 2);
+        assert_eq!(moves_to_order(&reversed), 4);
+    }
+}
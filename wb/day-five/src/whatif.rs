@@ -0,0 +1,267 @@
+use crate::correct_order;
+use page_rules::{Page, Rules};
+use std::fmt;
+
+/// How one instruction was affected by a rule change. Middle pages are taken after correcting
+/// the instruction, and are `None` when its rules form a cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change<T> {
+    pub instruction: usize,
+    pub was_valid: bool,
+    pub is_valid: bool,
+    pub middle_before: Option<T>,
+    pub middle_after: Option<T>,
+}
+
+/// Rules and instructions that stay checked while single rules are added or removed. A change
+/// to the rule `a|b` only re-checks the instructions that contain both `a` and `b`.
+pub struct Session<T> {
+    rules: Rules<T>,
+    instructions: Vec<Vec<T>>,
+    ids: Vec<Vec<usize>>,
+    /// For every page id, the instructions it appears in.
+    containing: Vec<Vec<usize>>,
+    /// Number of broken rules per instruction.
+    broken: Vec<usize>,
+    /// Middle page of every instruction after correcting it.
+    middles: Vec<Option<T>>,
+}
+
+impl<T: Page> Session<T> {
+    pub fn new(mut rules: Rules<T>, instructions: Vec<Vec<T>>) -> Session<T> {
+        for page in instructions.iter().flatten() {
+            rules.intern(page.clone());
+        }
+        let ids: Vec<Vec<usize>> = instructions.iter().map(|i| rules.ids(i)).collect();
+        let mut containing = vec![Vec::new(); rules.len()];
+        for (i, instr) in ids.iter().enumerate() {
+            for &id in instr {
+                if containing[id].last() != Some(&i) {
+                    containing[id].push(i);
+                }
+            }
+        }
+
+        let mut session = Session {
+            rules,
+            instructions,
+            ids,
+            containing,
+            broken: Vec::new(),
+            middles: Vec::new(),
+        };
+        session.broken = (0..session.instructions.len())
+            .map(|i| session.count_broken(i))
+            .collect();
+        session.middles = (0..session.instructions.len())
+            .map(|i| session.corrected_middle(i))
+            .collect();
+        session
+    }
+
+    pub fn is_valid(&self, instruction: usize) -> bool {
+        self.broken[instruction] == 0
+    }
+
+    fn count_broken(&self, instruction: usize) -> usize {
+        let ids = &self.ids[instruction];
+        (0..ids.len())
+            .flat_map(|i| (i + 1..ids.len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| self.rules.precedes(ids[j], ids[i]))
+            .count()
+    }
+
+    fn corrected_middle(&self, instruction: usize) -> Option<T> {
+        let instr = &self.instructions[instruction];
+        if self.is_valid(instruction) {
+            return Some(instr[instr.len() / 2].clone());
+        }
+        correct_order(instr, &self.rules)
+            .ok()
+            .map(|ordered| ordered[instr.len() / 2].clone())
+    }
+
+    /// The instructions with both pages, in order.
+    fn containing_both(&self, a: usize, b: usize) -> Vec<usize> {
+        let (with_a, with_b) = (&self.containing[a], &self.containing[b]);
+        with_a
+            .iter()
+            .filter(|i| with_b.binary_search(i).is_ok())
+            .copied()
+            .collect()
+    }
+
+    /// Re-checks the instructions with both pages of the rule `a|b`, which was just added or
+    /// removed. Like `count_broken`, every pair of steps with `b` before `a` counts as one
+    /// broken rule, so that is how much the count of an instruction changes.
+    fn recheck(&mut self, a: usize, b: usize, added: bool) -> Vec<Change<T>> {
+        let mut changes = Vec::new();
+        for i in self.containing_both(a, b) {
+            let (mut bs_so_far, mut pairs) = (0, 0);
+            for &id in &self.ids[i] {
+                if id == a {
+                    pairs += bs_so_far;
+                }
+                if id == b {
+                    bs_so_far += 1;
+                }
+            }
+            let was_valid = self.is_valid(i);
+            if added {
+                self.broken[i] += pairs;
+            } else {
+                self.broken[i] -= pairs;
+            }
+            let middle_before = self.middles[i].clone();
+            self.middles[i] = self.corrected_middle(i);
+            let change = Change {
+                instruction: i,
+                was_valid,
+                is_valid: self.is_valid(i),
+                middle_before,
+                middle_after: self.middles[i].clone(),
+            };
+            if change.was_valid != change.is_valid || change.middle_before != change.middle_after {
+                changes.push(change);
+            }
+        }
+        changes
+    }
+
+    /// Adds the rule `before|after` and returns the instructions whose validity or corrected
+    /// middle page changed.
+    pub fn add_rule(&mut self, before: T, after: T) -> Vec<Change<T>> {
+        let new = self.rules.add(before.clone(), after.clone());
+        self.containing.resize(self.rules.len(), Vec::new());
+        if !new {
+            return Vec::new();
+        }
+        let (a, b) = (
+            self.rules.id(&before).unwrap(),
+            self.rules.id(&after).unwrap(),
+        );
+        self.recheck(a, b, true)
+    }
+
+    /// Removes the rule `before|after` and returns the instructions whose validity or corrected
+    /// middle page changed.
+    pub fn remove_rule(&mut self, before: &T, after: &T) -> Vec<Change<T>> {
+        if !self.rules.remove(before, after) {
+            return Vec::new();
+        }
+        let (a, b) = (
+            self.rules.id(before).unwrap(),
+            self.rules.id(after).unwrap(),
+        );
+        self.recheck(a, b, false)
+    }
+}
+
+impl<T: Page + Copy + Into<u64>> Session<T> {
+    /// Sum of the middle pages of the valid instructions.
+    pub fn valid_sum(&self) -> u64 {
+        (0..self.instructions.len())
+            .filter(|&i| self.is_valid(i))
+            .map(|i| self.middles[i].unwrap().into())
+            .sum()
+    }
+
+    /// Sum of the middle pages of the invalid instructions after correcting them, or `None` if
+    /// any of them cannot be corrected.
+    pub fn corrected_sum(&self) -> Option<u64> {
+        (0..self.instructions.len())
+            .filter(|&i| !self.is_valid(i))
+            .map(|i| self.middles[i].map(Into::into))
+            .sum()
+    }
+}
+
+impl<T: Page> fmt::Display for Change<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let validity = |valid: bool| if valid { "valid" } else { "invalid" };
+        let middle = |m: &Option<T>| m.as_ref().map_or("cycle".to_string(), |m| m.to_string());
+        write!(
+            f,
+            "Instruction {}: {} -> {}, middle page {} -> {}",
+            self.instruction,
+            validity(self.was_valid),
+            validity(self.is_valid),
+            middle(&self.middle_before),
+            middle(&self.middle_after)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_remove_rules() {
+        let (rules, instructions) = crate::read_rules_and_instructions::<u32>("test.txt").unwrap();
+        let mut session = Session::new(rules, instructions.clone());
+        assert_eq!(session.valid_sum(), 143);
+        assert_eq!(session.corrected_sum(), Some(123));
+
+        let changes = session.remove_rule(&97, &75);
+        assert_eq!(
+            changes,
+            vec![Change {
+                instruction: 3,
+                was_valid: false,
+                is_valid: true,
+                middle_before: Some(47),
+                middle_after: Some(47),
+            }]
+        );
+        assert_eq!(session.valid_sum(), 143 + 47);
+        assert_eq!(session.corrected_sum(), Some(123 - 47));
+
+        // Contradicts 53|29, so the first instruction can no longer be put in order
+        let changes = session.add_rule(29, 53);
+        assert_eq!(changes[0].instruction, 0);
+        assert_eq!(changes[0].middle_after, None);
+        assert_eq!(session.corrected_sum(), None);
+        assert!(session.add_rule(29, 53).is_empty());
+
+        // A new page is picked up, and a rule with it changes nothing until it is used
+        assert!(session.add_rule(12, 97).is_empty());
+
+        let fresh = Session::new(session.rules.clone(), instructions);
+        let validity = |s: &Session<u32>| (0..6).map(|i| s.is_valid(i)).collect::<Vec<_>>();
+        assert_eq!(validity(&session), validity(&fresh));
+        assert_eq!(session.middles, fresh.middles);
+    }
+
+    #[test]
+    fn test_edits_match_fresh_session() {
+        let mut state: u64 = 0x0505;
+        let mut random = |n: u64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % n
+        };
+        // Few pages, so instructions repeat pages and rules often form cycles
+        let instructions: Vec<Vec<u32>> = (0..20)
+            .map(|_| (0..1 + random(7)).map(|_| random(6) as u32).collect())
+            .collect();
+        let mut rules = Rules::new();
+        for _ in 0..8 {
+            rules.add(random(6) as u32, random(6) as u32);
+        }
+
+        let mut session = Session::new(rules, instructions.clone());
+        for _ in 0..500 {
+            let (before, after) = (random(7) as u32, random(7) as u32);
+            if random(2) == 0 {
+                session.add_rule(before, after);
+            } else {
+                session.remove_rule(&before, &after);
+            }
+            let fresh = Session::new(session.rules.clone(), instructions.clone());
+            assert_eq!(session.broken, fresh.broken);
+            assert_eq!(session.middles, fresh.middles);
+        }
+    }
+}
@@ -1,4 +1,11 @@
-use std::fs;
+mod render;
+mod search;
+mod template;
+
+use render::Highlights;
+use search::{find_words, Grid, Match};
+use std::{env, fs, process};
+use template::{find_template, Placement, Symmetry, Template};
 
 #[cfg(feature = "dev")]
 const FILE_NAME: &str = "test.txt";
@@ -7,10 +14,10 @@ const FILE_NAME: &str = "input.txt";
 
 fn read_string_matrix(file_path: &str) -> Vec<Vec<char>> {
     let string_input = fs::read_to_string(file_path).expect("Failed to read file");
-    return string_input
+    string_input
         .lines()
         .map(|line| line.chars().collect())
-        .collect();
+        .collect()
 }
 
 fn generate_direction_vecs(input: &[i32]) -> Vec<[i32; 2]> {
@@ -25,113 +32,110 @@ fn generate_direction_vecs(input: &[i32]) -> Vec<[i32; 2]> {
     combinations
 }
 
-fn check_in_bound(i: usize, j: usize, m: &Vec<Vec<char>>, dv: &[i32; 2]) -> bool {
-    let new_row = i as i32 + dv[0] * 3;
-    let new_col = j as i32 + dv[1] * 3;
-
-    let row_positive_dir = dv[0].is_positive();
-    let row_negative_within_bounds = (dv[0] * 3).abs() <= i as i32;
-    let row_in_bound =
-        (new_row < m.len() as i32) && (row_positive_dir || row_negative_within_bounds);
-
-    let col_positive_dir = dv[1].is_positive();
-    let col_negative_within_bounds = (dv[1] * 3).abs() <= j as i32;
-    let col_in_bound =
-        (new_col < m[i].len() as i32) && (col_positive_dir || col_negative_within_bounds);
-
-    row_in_bound && col_in_bound
+fn find_xmas(grid: &Grid) -> Vec<Match> {
+    let direction_vecs = generate_direction_vecs(&[1, 0, -1]);
+    find_words(grid, &["XMAS"], &direction_vecs)
 }
 
-fn star_check(
-    i: usize,
-    j: usize,
-    m: &Vec<Vec<char>>,
-    check: [char; 3],
-    dir_vecs: &Vec<[i32; 2]>,
-) -> u32 {
-    let mut found_xmas = 0;
-    let muls = [1, 2, 3];
-
-    'dv_loop: for dv in dir_vecs.iter() {
-        if check_in_bound(i, j, m, dv) {
-            for (factor, xc) in muls.iter().zip(check.iter()) {
-                let row_i = (i as i32 + dv[0] * factor) as usize;
-                let col_i = (j as i32 + dv[1] * factor) as usize;
-                if m[row_i][col_i] == *xc {
-                    if *factor == 3 {
-                        found_xmas += 1;
-                        #[cfg(feature = "dev")]
-                        println!("Found at {},{}", i, j);
-                    }
-                } else {
-                    continue 'dv_loop;
-                }
-            }
-        }
-    }
-    found_xmas
+fn find_xs_of_mas(grid: &Grid) -> Vec<Placement> {
+    let x_mas = Template::parse(&["M.S", ".A.", "M.S"], '.');
+    find_template(grid, &x_mas, Symmetry::Rotations)
 }
 
-fn count_xmas(matrix: &Vec<Vec<char>>) -> u32 {
-    let mut n_xmas: u32 = 0;
-    let xmas_check = ['M', 'A', 'S'];
-    let direction_vecs = generate_direction_vecs(&vec![1, 0, -1]);
-    for (i, _) in matrix.iter().enumerate() {
-        for (j, _) in matrix[i].iter().enumerate() {
-            if matrix[i][j] == 'X' {
-                n_xmas += star_check(i, j, matrix, xmas_check, &direction_vecs);
-            }
-        }
+/// Counts the XMASes and the X-MASes of the puzzle, highlighting every one of them.
+fn highlight_puzzle(grid: &Grid, highlights: &mut Highlights) -> (usize, usize) {
+    let xmasses = find_xmas(grid);
+    for m in &xmasses {
+        highlights.add_word(grid, m, 4);
     }
-    n_xmas
-}
-
-fn generate_rotations<T: Clone>(input: &[T]) -> Vec<Vec<T>> {
-    let mut rotations = Vec::new();
-    let len = input.len();
-    for i in 0..len {
-        let rotation = input[i..]
-            .iter()
-            .chain(input[..i].iter())
-            .cloned()
-            .collect();
-        rotations.push(rotation);
+    let xs_of_mas = find_xs_of_mas(grid);
+    for placement in &xs_of_mas {
+        highlights.add_placement(placement);
     }
-    rotations
+    (xmasses.len(), xs_of_mas.len())
 }
 
-fn get_corners(i: usize, j: usize, matrix: &Vec<Vec<char>>) -> Vec<char> {
-    vec![
-        matrix[i - 1][j + 1],
-        matrix[i + 1][j + 1],
-        matrix[i + 1][j - 1],
-        matrix[i - 1][j - 1],
-    ]
-}
-
-fn count_xs_of_mas(matrix: &Vec<Vec<char>>) -> u32 {
-    let mut n_xs_of_mas: u32 = 0;
-    let corner_patterns = generate_rotations(&vec!['M', 'M', 'S', 'S']);
-    for i in 1..matrix.len() - 1 {
-        for j in 1..matrix[i].len() - 1 {
-            if matrix[i][j] == 'A' {
-                let corners = get_corners(i, j, matrix);
-                if corner_patterns.iter().any(|cp| &corners == cp) {
-                    n_xs_of_mas += 1;
-                    #[cfg(feature = "dev")]
-                    println!("Found one at i: {}, j: {}", i, j)
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let flag = |name: &str| args.iter().any(|a| a == name);
+    let option = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|i| match args.get(i + 1) {
+                Some(value) => value,
+                None => {
+                    eprintln!("Usage error: {} needs a value", name);
+                    process::exit(2)
+                }
+            })
+    };
+
+    // `--wrap` lets matches cross the edges of the grid, and `--mask FILE` blocks the cells
+    // marked with `#` in a file of the same shape as the grid
+    let mut grid = Grid::new(read_string_matrix(FILE_NAME));
+    grid.wrap = flag("--wrap");
+    if let Some(path) = option("--mask") {
+        for (i, row) in read_string_matrix(path).iter().enumerate() {
+            for (j, &c) in row.iter().enumerate() {
+                if c == '#' {
+                    grid.block((i, j));
                 }
             }
         }
     }
-    n_xs_of_mas
-}
+    let mut highlights = Highlights::new(&grid);
+
+    // `--words A,B,C` searches for other words in the same grid, `--dictionary FILE` for
+    // every word in a file with one word per line
+    let word_list = match (option("--words"), option("--dictionary")) {
+        (Some(words), _) => Some(words.replace(',', "\n")),
+        (None, Some(path)) => Some(fs::read_to_string(path).expect("Failed to read dictionary")),
+        (None, None) => None,
+    };
+    if let Some(word_list) = word_list {
+        let words: Vec<&str> = word_list.lines().map(str::trim).collect();
+        let matches = find_words(&grid, &words, &generate_direction_vecs(&[1, 0, -1]));
+        let mut found = vec![0; words.len()];
+        for m in &matches {
+            found[m.word] += 1;
+            highlights.add_word(&grid, m, words[m.word].chars().count());
+        }
+        for (word, found) in words.iter().zip(found) {
+            println!("{}: {}", word, found);
+        }
+    } else if let Some(template) = option("--template") {
+        // `--template M.S/.A./M.S` counts placements of a pattern, `.` matching anything, in
+        // its rotations, or with `--reflections` also in its mirror images
+        let rows: Vec<&str> = template.split('/').collect();
+        if rows.iter().all(|row| row.is_empty()) {
+            eprintln!("Usage error: --template needs at least one cell");
+            process::exit(2);
+        }
+        let symmetry = if flag("--reflections") {
+            Symmetry::RotationsAndReflections
+        } else {
+            Symmetry::Rotations
+        };
+        let placements = find_template(&grid, &Template::parse(&rows, '.'), symmetry);
+        for placement in &placements {
+            highlights.add_placement(placement);
+        }
+        println!("{}: {}", template, placements.len());
+    } else {
+        let (xmasses, xs_of_mas) = highlight_puzzle(&grid, &mut highlights);
+        println!("xmasses: {}, xs of mas: {}", xmasses, xs_of_mas);
+    }
 
-fn main() {
-    let xmas_matrix = read_string_matrix(FILE_NAME);
-    let xmas_result = count_xmas(&xmas_matrix);
-    let xs_of_mas_result = count_xs_of_mas(&xmas_matrix);
-    println!("xmasses: {}, xs of mas: {}", xmas_result, xs_of_mas_result);
+    // `--render` draws the matches like the puzzle does, `--color` colours them by direction
+    // and `--html FILE` writes the coloured grid to a web page
+    if flag("--color") {
+        print!("{}", highlights.ansi(&grid));
+    } else if flag("--render") {
+        print!("{}", highlights.plain(&grid));
+    }
+    if let Some(path) = option("--html") {
+        fs::write(path, highlights.html(&grid)).expect("Failed to write HTML");
+    }
 }
 
 #[cfg(test)]
@@ -155,26 +159,17 @@ mod tests {
     }
 
     #[test]
-    fn test_check_in_bound() {
-        let matrix = vec![
-            vec!['X', 'M', 'A', 'S'],
-            vec!['M', 'A', 'S', 'X'],
-            vec!['A', 'S', 'X', 'M'],
-            vec!['S', 'X', 'M', 'A'],
-        ];
-        assert!(check_in_bound(0, 0, &matrix, &[1, 1]));
-        assert!(!check_in_bound(0, 0, &matrix, &[3, 3]));
-    }
-
-    #[test]
-    fn test_generate_rotations() {
-        let input = vec!['M', 'M', 'S', 'S'];
-        let expected = vec![
-            vec!['M', 'M', 'S', 'S'],
-            vec!['M', 'S', 'S', 'M'],
-            vec!['S', 'S', 'M', 'M'],
-            vec!['S', 'M', 'M', 'S'],
-        ];
-        assert_eq!(generate_rotations(&input), expected);
+    fn test_highlight_puzzle() {
+        let grid = Grid::new(read_string_matrix("test.txt"));
+        let mut highlights = Highlights::new(&grid);
+        assert_eq!(highlight_puzzle(&grid, &mut highlights), (18, 9));
+
+        let plain = highlights.plain(&grid);
+        let rows: Vec<&[u8]> = plain.lines().map(str::as_bytes).collect();
+        for placement in find_xs_of_mas(&grid) {
+            for (i, j) in placement.cells {
+                assert_eq!(rows[i][j] as char, grid.get((i, j)));
+            }
+        }
     }
 }
@@ -0,0 +1,177 @@
+use crate::search::{Direction, Grid, Match, Position};
+use crate::template::Placement;
+use std::fmt::Write;
+
+/// How a highlighted cell is coloured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    /// Part of a word read in this direction.
+    Word(Direction),
+    /// Part of a template placement.
+    Shape,
+    /// Part of several matches that would be coloured differently.
+    Mixed,
+}
+
+impl Mark {
+    /// One of nine colour slots: the eight compass directions and shapes in the middle.
+    fn slot(&self) -> Option<usize> {
+        match self {
+            Mark::Word(dv) => Some(((dv[0].signum() + 1) * 3 + dv[1].signum() + 1) as usize),
+            Mark::Shape => Some(4),
+            Mark::Mixed => None,
+        }
+    }
+
+    fn ansi(&self) -> &'static str {
+        const COLOURS: [&str; 9] = ["31", "32", "33", "34", "93", "35", "36", "91", "94"];
+        self.slot().map_or("1;97", |slot| COLOURS[slot])
+    }
+
+    fn css(&self) -> &'static str {
+        const COLOURS: [&str; 9] = [
+            "#e6194b", "#3cb44b", "#ffe119", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6",
+            "#469990",
+        ];
+        self.slot().map_or("#ffffff", |slot| COLOURS[slot])
+    }
+}
+
+/// The cells of a grid that belong to matches, for drawing the grid the way the puzzle does:
+/// matched letters are kept and everything else becomes `.`.
+pub struct Highlights {
+    marks: Vec<Vec<Option<Mark>>>,
+}
+
+impl Highlights {
+    pub fn new(grid: &Grid) -> Highlights {
+        let marks = (0..grid.rows()).map(|i| vec![None; grid.cols(i)]).collect();
+        Highlights { marks }
+    }
+
+    fn mark(&mut self, (i, j): Position, mark: Mark) {
+        let cell = &mut self.marks[i][j];
+        *cell = match *cell {
+            Some(old) if old != mark => Some(Mark::Mixed),
+            _ => Some(mark),
+        };
+    }
+
+    pub fn add_word(&mut self, grid: &Grid, m: &Match, len: usize) {
+        for n in 0..len {
+            if let Some(pos) = grid.step(m.start, m.direction, n) {
+                self.mark(pos, Mark::Word(m.direction));
+            }
+        }
+    }
+
+    pub fn add_placement(&mut self, placement: &Placement) {
+        for &pos in &placement.cells {
+            self.mark(pos, Mark::Shape);
+        }
+    }
+
+    /// Each row as the characters to draw and their marks. Matched cells keep their letter,
+    /// blocked cells are drawn as `#` and all others as `.`.
+    fn cells<'a>(&'a self, grid: &'a Grid) -> impl Iterator<Item = Vec<(char, Option<Mark>)>> + 'a {
+        self.marks.iter().enumerate().map(move |(i, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(j, &mark)| match mark {
+                    Some(mark) => (grid.get((i, j)), Some(mark)),
+                    None if grid.is_blocked((i, j)) => ('#', None),
+                    None => ('.', None),
+                })
+                .collect()
+        })
+    }
+
+    pub fn plain(&self, grid: &Grid) -> String {
+        let mut out = String::new();
+        for row in self.cells(grid) {
+            for (c, _) in row {
+                out.push(c);
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Like `plain`, with each letter coloured by the direction of its word.
+    pub fn ansi(&self, grid: &Grid) -> String {
+        let mut out = String::new();
+        for row in self.cells(grid) {
+            for (c, mark) in row {
+                match mark {
+                    Some(mark) => write!(out, "\x1b[{}m{}\x1b[0m", mark.ansi(), c).unwrap(),
+                    None => write!(out, "\x1b[2m{}\x1b[0m", c).unwrap(),
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn html(&self, grid: &Grid) -> String {
+        let mut out = String::from(concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Word search</title>\n",
+            "<style>body { background: #0f0f23; color: #666; } ",
+            "pre { font-size: 14px; line-height: 1.1; }</style>\n</head>\n<body>\n<pre>\n"
+        ));
+        for row in self.cells(grid) {
+            for (c, mark) in row {
+                match mark {
+                    Some(mark) => write!(
+                        out,
+                        "<span style=\"color: {}; font-weight: bold\">{}</span>",
+                        mark.css(),
+                        escape_html(c)
+                    )
+                    .unwrap(),
+                    None => out.push(c),
+                }
+            }
+            out.push('\n');
+        }
+        out.push_str("</pre>\n</body>\n</html>\n");
+        out
+    }
+}
+
+fn escape_html(c: char) -> String {
+    match c {
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '&' => "&amp;".to_string(),
+        c => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::find_words;
+
+    #[test]
+    fn test_plain() {
+        let grid = Grid::new(crate::read_string_matrix("test.txt"));
+        let directions = crate::generate_direction_vecs(&[1, 0, -1]);
+        let mut highlights = Highlights::new(&grid);
+        for m in find_words(&grid, &["XMAS"], &directions) {
+            highlights.add_word(&grid, &m, 4);
+        }
+        let expected = [
+            "....XXMAS.",
+            ".SAMXMS...",
+            "...S..A...",
+            "..A.A.MS.X",
+            "XMASAMX.MM",
+            "X.....XA.A",
+            "S.S.S.S.SS",
+            ".A.A.A.A.A",
+            "..M.M.M.MM",
+            ".X.X.XMASX",
+        ];
+        assert_eq!(highlights.plain(&grid), expected.join("\n") + "\n");
+    }
+}
@@ -0,0 +1,286 @@
+use aho_corasick::AhoCorasick;
+
+/// Row and column step of a search direction.
+pub type Direction = [i32; 2];
+pub type Position = (usize, usize);
+
+#[derive(Debug, Clone)]
+pub struct Grid {
+    cells: Vec<Vec<char>>,
+    blocked: Vec<Vec<bool>>,
+    /// Whether the grid is a torus, where stepping off one edge continues at the opposite one.
+    pub wrap: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Match {
+    /// Index of the matched word in the query.
+    pub word: usize,
+    pub start: Position,
+    pub direction: Direction,
+}
+
+/// A straight run of open cells. A cyclic line continues from its last cell to its first.
+struct Line {
+    cells: Vec<Position>,
+    cyclic: bool,
+}
+
+impl Grid {
+    pub fn new(cells: Vec<Vec<char>>) -> Grid {
+        let blocked = cells.iter().map(|row| vec![false; row.len()]).collect();
+        Grid {
+            cells,
+            blocked,
+            wrap: false,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn cols(&self, i: usize) -> usize {
+        self.cells[i].len()
+    }
+
+    pub fn get(&self, (i, j): Position) -> char {
+        self.cells[i][j]
+    }
+
+    /// Blocks a cell, so that no match may pass through it.
+    pub fn block(&mut self, (i, j): Position) {
+        self.blocked[i][j] = true;
+    }
+
+    pub fn is_blocked(&self, (i, j): Position) -> bool {
+        self.blocked[i][j]
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Position> + '_ {
+        (0..self.rows()).flat_map(move |i| (0..self.cells[i].len()).map(move |j| (i, j)))
+    }
+
+    /// The cell `steps` steps from `pos` in direction `dv`, if it is inside the grid, or the
+    /// grid wraps, and the cell is not blocked.
+    pub fn step(&self, (i, j): Position, dv: Direction, steps: usize) -> Option<Position> {
+        let mut row = i as i64 + i64::from(dv[0]) * steps as i64;
+        let mut col = j as i64 + i64::from(dv[1]) * steps as i64;
+        if self.wrap {
+            row = row.rem_euclid(self.rows() as i64);
+            col = col.rem_euclid(self.cols(row as usize) as i64);
+        }
+        let row_in_bound = (0..self.rows() as i64).contains(&row);
+        if !row_in_bound || !(0..self.cols(row as usize) as i64).contains(&col) {
+            return None;
+        }
+        let pos = (row as usize, col as usize);
+        (!self.is_blocked(pos)).then_some(pos)
+    }
+
+    /// Every maximal straight run of open cells in direction `dv`. Runs start at a cell whose
+    /// predecessor in that direction is outside the grid or blocked. On a wrapping grid the
+    /// remaining cells lie on cycles without any blocked cell, which become cyclic lines.
+    fn lines(&self, dv: Direction) -> Vec<Line> {
+        let back = [-dv[0], -dv[1]];
+        let mut lines: Vec<Line> = self
+            .positions()
+            .filter(|&pos| !self.is_blocked(pos) && self.step(pos, back, 1).is_none())
+            .map(|start| Line {
+                cells: std::iter::successors(Some(start), |&pos| self.step(pos, dv, 1)).collect(),
+                cyclic: false,
+            })
+            .collect();
+        if !self.wrap {
+            return lines;
+        }
+
+        let mut seen: Vec<Vec<bool>> = self.blocked.clone();
+        for pos in lines.iter().flat_map(|line| &line.cells) {
+            seen[pos.0][pos.1] = true;
+        }
+        for start in self.positions() {
+            if seen[start.0][start.1] {
+                continue;
+            }
+            let mut cells = vec![start];
+            let mut pos = start;
+            while let Some(next) = self.step(pos, dv, 1).filter(|&next| next != start) {
+                cells.push(next);
+                pos = next;
+            }
+            for pos in &cells {
+                seen[pos.0][pos.1] = true;
+            }
+            lines.push(Line {
+                cells,
+                cyclic: true,
+            });
+        }
+        lines
+    }
+}
+
+/// Finds every occurrence of every word, reading in any of the given directions. The grid is
+/// cut into its lines in each direction once, and all words are searched for in those lines
+/// at the same time with an Aho-Corasick automaton.
+pub fn find_words(grid: &Grid, words: &[&str], directions: &[Direction]) -> Vec<Match> {
+    let (patterns, word_indices): (Vec<&str>, Vec<usize>) = words
+        .iter()
+        .enumerate()
+        .filter(|(_, w)| !w.is_empty())
+        .map(|(i, &w)| (w, i))
+        .unzip();
+    let automaton = AhoCorasick::new(&patterns).expect("Could not build automaton");
+    let longest = patterns
+        .iter()
+        .map(|w| w.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut matches = Vec::new();
+    for (d, &direction) in directions.iter().enumerate() {
+        for line in grid.lines(direction) {
+            // A cyclic line is read around once more, far enough for any word to cross over
+            let len = line.cells.len();
+            let read = if line.cyclic { len + longest - 1 } else { len };
+            let mut text = String::new();
+            let mut char_at_byte = Vec::new();
+            for n in 0..read {
+                text.push(grid.get(line.cells[n % len]));
+                char_at_byte.resize(text.len(), n);
+            }
+            for m in automaton.find_overlapping_iter(&text) {
+                let n = char_at_byte[m.start()];
+                if n >= len {
+                    continue;
+                }
+                let start = line.cells[n];
+                #[cfg(feature = "dev")]
+                println!(
+                    "Found {} at {:?} going {:?}",
+                    patterns[m.pattern()],
+                    start,
+                    direction
+                );
+                let word = word_indices[m.pattern()];
+                matches.push((
+                    d,
+                    Match {
+                        word,
+                        start,
+                        direction,
+                    },
+                ));
+            }
+        }
+    }
+    matches.sort_by_key(|(d, m)| (m.start, m.word, *d));
+    matches.into_iter().map(|(_, m)| m).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step() {
+        let grid = Grid::new(vec![
+            vec!['X', 'M', 'A', 'S'],
+            vec!['M', 'A', 'S', 'X'],
+            vec!['A', 'S', 'X', 'M'],
+            vec!['S', 'X', 'M', 'A'],
+        ]);
+        assert_eq!(grid.step((0, 0), [1, 1], 3), Some((3, 3)));
+        assert_eq!(grid.step((0, 0), [3, 3], 3), None);
+        assert_eq!(grid.step((0, 3), [0, 1], 1), None);
+        assert_eq!(grid.step((3, 0), [-1, 1], 3), Some((0, 3)));
+    }
+
+    #[test]
+    fn test_find_words() {
+        let grid = Grid::new(vec![vec!['X', 'M', 'A', 'S'], vec!['S', 'A', 'M', 'X']]);
+        let directions = [[0, 1], [0, -1], [1, 0]];
+        let expected = vec![
+            Match {
+                word: 0,
+                start: (0, 0),
+                direction: [0, 1],
+            },
+            Match {
+                word: 1,
+                start: (0, 2),
+                direction: [0, -1],
+            },
+            Match {
+                word: 1,
+                start: (1, 1),
+                direction: [0, 1],
+            },
+            Match {
+                word: 0,
+                start: (1, 3),
+                direction: [0, -1],
+            },
+        ];
+        assert_eq!(find_words(&grid, &["XMAS", "AMX"], &directions), expected);
+    }
+
+    fn brute_force(grid: &Grid, words: &[&str], directions: &[Direction]) -> Vec<Match> {
+        let mut expected = Vec::new();
+        for start in grid.positions() {
+            for (word, w) in words.iter().enumerate() {
+                for &direction in directions {
+                    let spelled: Option<String> = (0..w.len())
+                        .map(|n| grid.step(start, direction, n).map(|pos| grid.get(pos)))
+                        .collect();
+                    if spelled.as_deref() == Some(*w) {
+                        expected.push(Match {
+                            word,
+                            start,
+                            direction,
+                        });
+                    }
+                }
+            }
+        }
+        expected
+    }
+
+    #[test]
+    fn test_find_words_matches_brute_force() {
+        let mut grid = Grid::new(crate::read_string_matrix("test.txt"));
+        let words = [
+            "XMAS",
+            "MAS",
+            "SAMX",
+            "AXA",
+            "MM",
+            "S",
+            "AMMXMAS",
+            "XMASAMXAMMXMAS",
+        ];
+        let directions = crate::generate_direction_vecs(&[1, 0, -1]);
+        let expected = brute_force(&grid, &words, &directions);
+        assert_eq!(find_words(&grid, &words, &directions), expected);
+
+        grid.wrap = true;
+        let expected = brute_force(&grid, &words, &directions);
+        assert!(expected.contains(&Match {
+            word: 7,
+            start: (4, 0),
+            direction: [0, 1]
+        }));
+        assert_eq!(find_words(&grid, &words, &directions), expected);
+
+        for pos in [(0, 4), (4, 4), (9, 9), (3, 0)] {
+            grid.block(pos);
+        }
+        let expected = brute_force(&grid, &words, &directions);
+        assert_eq!(find_words(&grid, &words, &directions), expected);
+
+        grid.wrap = false;
+        let expected = brute_force(&grid, &words, &directions);
+        assert_eq!(find_words(&grid, &words, &directions), expected);
+    }
+}
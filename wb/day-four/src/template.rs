@@ -0,0 +1,191 @@
+use crate::search::{Grid, Position};
+use std::collections::HashSet;
+
+/// A 2D pattern of letters, where `None` cells match anything.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Template {
+    cells: Vec<Vec<Option<char>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    Identity,
+    Rotations,
+    RotationsAndReflections,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    /// Grid cell under the top left corner of the matching orientation.
+    pub anchor: Position,
+    /// Index into `Template::orientations`.
+    pub orientation: usize,
+    /// Grid cells under the template's letters, sorted.
+    pub cells: Vec<Position>,
+}
+
+impl Template {
+    /// Builds a template from rows of text, where `wildcard` matches any letter.
+    pub fn parse(rows: &[&str], wildcard: char) -> Template {
+        let cells = rows
+            .iter()
+            .map(|row| row.chars().map(|c| (c != wildcard).then_some(c)).collect())
+            .collect();
+        Template { cells }
+    }
+
+    fn width(&self) -> usize {
+        self.cells.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    fn get(&self, i: usize, j: usize) -> Option<char> {
+        self.cells[i].get(j).copied().flatten()
+    }
+
+    /// The template turned a quarter clockwise.
+    fn rotate(&self) -> Template {
+        let height = self.cells.len();
+        let cells = (0..self.width())
+            .map(|i| (0..height).map(|j| self.get(height - 1 - j, i)).collect())
+            .collect();
+        Template { cells }
+    }
+
+    /// The template mirrored left to right.
+    fn reflect(&self) -> Template {
+        let width = self.width();
+        let cells = (0..self.cells.len())
+            .map(|i| (0..width).map(|j| self.get(i, width - 1 - j)).collect())
+            .collect();
+        Template { cells }
+    }
+
+    /// Every distinct orientation of the template under `symmetry`. Orientations that look
+    /// the same, as for a symmetric template, are only listed once.
+    pub fn orientations(&self, symmetry: Symmetry) -> Vec<Template> {
+        let mut bases = vec![self.clone()];
+        if symmetry == Symmetry::RotationsAndReflections {
+            bases.push(self.reflect());
+        }
+        let turns = if symmetry == Symmetry::Identity { 1 } else { 4 };
+
+        let mut orientations: Vec<Template> = Vec::new();
+        for base in bases {
+            let rotations = std::iter::successors(Some(base), |t| Some(t.rotate())).take(turns);
+            for rotation in rotations {
+                if !orientations.contains(&rotation) {
+                    orientations.push(rotation);
+                }
+            }
+        }
+        orientations
+    }
+
+    /// The grid cells under the template's letters with its top left corner at `anchor`, if
+    /// every letter matches.
+    fn match_at(&self, grid: &Grid, anchor: Position) -> Option<Vec<Position>> {
+        let mut cells = Vec::new();
+        for (i, row) in self.cells.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                let Some(letter) = cell else {
+                    continue;
+                };
+                let pos = grid.step(anchor, [i as i32, j as i32], 1)?;
+                if grid.get(pos) != *letter {
+                    return None;
+                }
+                cells.push(pos);
+            }
+        }
+        cells.sort();
+        Some(cells)
+    }
+}
+
+/// Finds every placement of the template in any of its orientations under `symmetry`. A set
+/// of grid cells matched by several orientations counts as one placement.
+pub fn find_template(grid: &Grid, template: &Template, symmetry: Symmetry) -> Vec<Placement> {
+    let orientations = template.orientations(symmetry);
+    let mut seen = HashSet::new();
+    let mut placements = Vec::new();
+    for anchor in grid.positions() {
+        for (orientation, t) in orientations.iter().enumerate() {
+            if let Some(cells) = t.match_at(grid, anchor) {
+                if seen.insert(cells.clone()) {
+                    #[cfg(feature = "dev")]
+                    println!("Found orientation {} at {:?}", orientation, anchor);
+                    placements.push(Placement {
+                        anchor,
+                        orientation,
+                        cells,
+                    });
+                }
+            }
+        }
+    }
+    placements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orientations() {
+        let x_mas = Template::parse(&["M.S", ".A.", "M.S"], '.');
+        let expected = vec![
+            Template::parse(&["M.S", ".A.", "M.S"], '.'),
+            Template::parse(&["M.M", ".A.", "S.S"], '.'),
+            Template::parse(&["S.M", ".A.", "S.M"], '.'),
+            Template::parse(&["S.S", ".A.", "M.M"], '.'),
+        ];
+        assert_eq!(x_mas.orientations(Symmetry::Rotations), expected);
+        assert_eq!(
+            x_mas.orientations(Symmetry::RotationsAndReflections),
+            expected
+        );
+
+        let plus = Template::parse(&[".A.", "AAA", ".A."], '.');
+        assert_eq!(
+            plus.orientations(Symmetry::RotationsAndReflections).len(),
+            1
+        );
+
+        let corner = Template::parse(&["AB", "C."], '.');
+        assert_eq!(corner.orientations(Symmetry::Identity).len(), 1);
+        assert_eq!(
+            corner.orientations(Symmetry::RotationsAndReflections).len(),
+            8
+        );
+    }
+
+    #[test]
+    fn test_find_template() {
+        let grid = Grid::new(crate::read_string_matrix("test.txt"));
+        let x_mas = Template::parse(&["M.S", ".A.", "M.S"], '.');
+        assert_eq!(find_template(&grid, &x_mas, Symmetry::Rotations).len(), 9);
+        assert_eq!(find_template(&grid, &x_mas, Symmetry::Identity).len(), 2);
+
+        let two_as = Template::parse(&["A", "A"], '.');
+        let all = find_template(&grid, &two_as, Symmetry::RotationsAndReflections);
+        let vertical = find_template(&grid, &two_as, Symmetry::Identity);
+        let horizontal = find_template(&grid, &Template::parse(&["AA"], '.'), Symmetry::Identity);
+        assert_eq!(all.len(), vertical.len() + horizontal.len());
+    }
+
+    #[test]
+    fn test_find_template_wrapping_and_masked() {
+        let rows = ["XSXM", "AXXX", "XSXM", "XXXX"];
+        let mut grid = Grid::new(rows.iter().map(|r| r.chars().collect()).collect());
+        let x_mas = Template::parse(&["M.S", ".A.", "M.S"], '.');
+        assert!(find_template(&grid, &x_mas, Symmetry::Rotations).is_empty());
+
+        grid.wrap = true;
+        let placements = find_template(&grid, &x_mas, Symmetry::Rotations);
+        assert_eq!(placements.len(), 1);
+        assert_eq!(placements[0].anchor, (0, 3));
+
+        grid.block((1, 0));
+        assert!(find_template(&grid, &x_mas, Symmetry::Rotations).is_empty());
+    }
+}
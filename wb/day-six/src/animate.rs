@@ -0,0 +1,323 @@
+use crate::lab::{Lab, Tile};
+use crate::render::Trail;
+use crate::Direction;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// One step of a guard, from a cell to the next one in a direction.
+pub type Move = (usize, usize, Direction);
+
+/// Indices into the palette. Trail colours follow, one for every set of directions.
+const FLOOR: u8 = 0;
+const OBSTACLE: u8 = 1;
+const ONE_WAY: u8 = 2;
+const TELEPORT: u8 = 3;
+const GUARD: u8 = 4;
+const TRAIL: u8 = 5;
+
+/// Colours of the directions, in `Direction::ALL` order.
+const DIRECTION_COLOURS: [[u8; 3]; 4] =
+    [[230, 25, 75], [60, 180, 75], [67, 99, 216], [255, 225, 25]];
+
+/// The colours of a frame. A cell walked in several directions gets the average of their
+/// colours.
+fn palette() -> Vec<[u8; 3]> {
+    let mut palette = vec![
+        [15, 15, 35],
+        [120, 120, 120],
+        [245, 130, 49],
+        [145, 30, 180],
+        [255, 255, 255],
+    ];
+    for mask in 1..16usize {
+        let colours: Vec<[u8; 3]> = (0..4)
+            .filter(|d| mask & 1 << d != 0)
+            .map(|d| DIRECTION_COLOURS[d])
+            .collect();
+        palette.push([0, 1, 2].map(|c| {
+            let sum: usize = colours.iter().map(|colour| colour[c] as usize).sum();
+            (sum / colours.len()) as u8
+        }));
+    }
+    palette
+}
+
+/// How the patrol is animated.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Width and height of a cell in pixels.
+    pub cell_size: usize,
+    /// Number of guard steps between two frames.
+    pub steps_per_frame: usize,
+    /// How long a frame of a GIF is shown, in milliseconds.
+    pub frame_delay: u16,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            cell_size: 4,
+            steps_per_frame: 1,
+            frame_delay: 20,
+        }
+    }
+}
+
+/// Where frames go: one animated GIF, or numbered PNG or PPM files next to the given path.
+/// The frame sizes were checked to fit the encoders' integer types.
+enum Output {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        width: u16,
+        height: u16,
+    },
+    Png {
+        path: PathBuf,
+        width: u32,
+        height: u32,
+    },
+    Ppm(PathBuf),
+}
+
+struct Animation<'a> {
+    lab: &'a Lab,
+    options: &'a Options,
+    palette: Vec<[u8; 3]>,
+    output: Output,
+    width: usize,
+    height: usize,
+    /// The frame being written, allocated once up front.
+    pixels: Vec<u8>,
+    frames: usize,
+}
+
+fn too_large(lab: &Lab, cell_size: usize) -> io::Error {
+    let (width, height) = lab.size();
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "Frames of {}x{} cells of {} pixels are too large, try a smaller --cell-size",
+            width, height, cell_size
+        ),
+    )
+}
+
+/// A frame side in pixels as the type an encoder takes, or an error if it does not fit.
+fn frame_side<T: TryFrom<usize>>(pixels: usize, lab: &Lab, cell_size: usize) -> io::Result<T> {
+    T::try_from(pixels).map_err(|_| too_large(lab, cell_size))
+}
+
+/// `walk.png` is written as `walk-0000.png`, `walk-0001.png` and so on.
+fn frame_path(path: &Path, frame: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}-{:04}.{}", stem, frame, extension))
+}
+
+impl Animation<'_> {
+    /// Draws the frame into `pixels` as palette indices, one byte per pixel.
+    fn draw(&mut self, trail: &Trail, guard: Option<usize>) {
+        let size = self.options.cell_size;
+        let pixels = &mut self.pixels;
+        pixels.clear();
+        pixels.resize(self.width * self.height, FLOOR);
+        for cell in 0..self.lab.cells() {
+            let colour = match self.lab.tile(cell) {
+                _ if guard == Some(cell) => GUARD,
+                Tile::Obstacle => OBSTACLE,
+                Tile::OneWay(_) => ONE_WAY,
+                Tile::Teleport(_) => TELEPORT,
+                Tile::Floor if trail.mask(cell) != 0 => TRAIL + trail.mask(cell) - 1,
+                Tile::Floor => continue,
+            };
+            let at = self.lab.position(cell);
+            for y in 0..size {
+                let row = (at.y as usize * size + y) * self.width + at.x as usize * size;
+                pixels[row..row + size].fill(colour);
+            }
+        }
+    }
+
+    fn write_frame(&mut self, trail: &Trail, guard: Option<usize>) -> io::Result<()> {
+        self.draw(trail, guard);
+        let pixels = &self.pixels;
+        match &mut self.output {
+            Output::Gif {
+                encoder,
+                width,
+                height,
+            } => {
+                let frame = gif::Frame {
+                    width: *width,
+                    height: *height,
+                    delay: self.options.frame_delay / 10,
+                    buffer: Cow::Borrowed(pixels),
+                    ..gif::Frame::default()
+                };
+                encoder.write_frame(&frame).map_err(io::Error::other)?;
+            }
+            Output::Png {
+                path,
+                width,
+                height,
+            } => {
+                let file = BufWriter::new(File::create(frame_path(path, self.frames))?);
+                let mut encoder = png::Encoder::new(file, *width, *height);
+                encoder.set_color(png::ColorType::Indexed);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_palette(self.palette.concat());
+                let mut writer = encoder.write_header()?;
+                writer.write_image_data(pixels)?;
+            }
+            Output::Ppm(path) => {
+                let mut file = BufWriter::new(File::create(frame_path(path, self.frames))?);
+                write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+                for &p in pixels {
+                    file.write_all(&self.palette[p as usize])?;
+                }
+                file.flush()?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// Writes the guard's moves as an animation, with a frame for the empty lab, one every
+/// `steps_per_frame` moves, and one at the end. The kind of output is picked by the extension
+/// of `path`: `.gif`, `.png` or `.ppm`. Returns the number of frames. Frames that are too
+/// large are an error before any output is created.
+pub fn export(path: &Path, lab: &Lab, moves: &[Move], options: &Options) -> io::Result<usize> {
+    let size = options.cell_size;
+    let (width, height) = lab.size();
+    let (width, height) = width
+        .checked_mul(size)
+        .zip(height.checked_mul(size))
+        .ok_or_else(|| too_large(lab, size))?;
+    let mut pixels = Vec::new();
+    width
+        .checked_mul(height)
+        .and_then(|area| pixels.try_reserve_exact(area).ok())
+        .ok_or_else(|| too_large(lab, size))?;
+
+    let palette = palette();
+    let output = match path.extension().and_then(|e| e.to_str()) {
+        Some("gif") => {
+            let width = frame_side(width, lab, size)?;
+            let height = frame_side(height, lab, size)?;
+            let file = BufWriter::new(File::create(path)?);
+            let mut encoder = gif::Encoder::new(file, width, height, &palette.concat())
+                .map_err(io::Error::other)?;
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .map_err(io::Error::other)?;
+            Output::Gif {
+                encoder,
+                width,
+                height,
+            }
+        }
+        Some("png") => Output::Png {
+            path: path.to_path_buf(),
+            width: frame_side(width, lab, size)?,
+            height: frame_side(height, lab, size)?,
+        },
+        Some("ppm") => Output::Ppm(path.to_path_buf()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Animations can only be written as .gif, .png or .ppm",
+            ))
+        }
+    };
+
+    let mut animation = Animation {
+        lab,
+        options,
+        palette,
+        output,
+        width,
+        height,
+        pixels,
+        frames: 0,
+    };
+    let mut trail = Trail::new(lab.cells());
+    animation.write_frame(&trail, moves.first().map(|&(from, _, _)| from))?;
+    for (i, &(from, to, direction)) in moves.iter().enumerate() {
+        trail.add(from, direction);
+        trail.add(to, direction);
+        if (i + 1) % options.steps_per_frame.max(1) == 0 || i + 1 == moves.len() {
+            animation.write_frame(&trail, Some(to))?;
+        }
+    }
+    Ok(animation.frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_frames() {
+        let map: Vec<Vec<char>> = [".#..", "...#", ".^..", "...."]
+            .iter()
+            .map(|r| r.chars().collect())
+            .collect();
+        let lab = Lab::from_matrix(&map).unwrap();
+        let at = |x, y| lab.index(crate::Vector2::new(x, y)).unwrap();
+        let moves = [
+            (at(1, 2), at(1, 1), Direction::Up),
+            (at(1, 1), at(2, 1), Direction::Right),
+            (at(2, 1), at(2, 2), Direction::Down),
+        ];
+        let options = Options {
+            cell_size: 2,
+            steps_per_frame: 2,
+            ..Options::default()
+        };
+
+        let dir = std::env::temp_dir().join(format!("day-six-frames-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let frames = export(&dir.join("walk.ppm"), &lab, &moves, &options).unwrap();
+        assert_eq!(frames, 3);
+
+        let last = std::fs::read(dir.join("walk-0002.ppm")).unwrap();
+        let header = b"P6\n8 8\n255\n";
+        assert_eq!(&last[..header.len()], header);
+        let pixel = |x: usize, y: usize| {
+            let i = header.len() + (y * 8 + x) * 3;
+            [last[i], last[i + 1], last[i + 2]]
+        };
+        let palette = palette();
+        assert_eq!(pixel(2, 4), DIRECTION_COLOURS[0]);
+        assert_eq!(pixel(3, 3), palette[(TRAIL + 0b0011 - 1) as usize]);
+        assert_eq!(pixel(4, 4), palette[GUARD as usize]);
+        assert_eq!(pixel(3, 1), palette[OBSTACLE as usize]);
+        assert_eq!(pixel(0, 0), palette[FLOOR as usize]);
+
+        assert!(export(&dir.join("walk.bmp"), &lab, &moves, &options).is_err());
+        // 4 cells of 20000 pixels do not fit in a GIF's 16-bit sizes
+        let huge = Options {
+            cell_size: 20_000,
+            ..options
+        };
+        let error = export(&dir.join("walk.gif"), &lab, &moves, &huge).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!dir.join("walk.gif").exists());
+        // Sides of 2^33 pixels would fit, but not the 2^66 pixels of a whole frame
+        let huge = Options {
+            cell_size: 1 << 31,
+            ..huge
+        };
+        for name in ["walk.png", "walk.ppm"] {
+            std::fs::remove_dir_all(&dir).unwrap();
+            std::fs::create_dir_all(&dir).unwrap();
+            let error = export(&dir.join(name), &lab, &moves, &huge).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
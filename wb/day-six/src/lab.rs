@@ -0,0 +1,175 @@
+use crate::{Direction, Vector2};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What is on a cell of the lab.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tile {
+    Floor,
+    Obstacle,
+    /// Can only be walked onto in its direction, and blocks the guard like an obstacle
+    /// otherwise. Drawn as one of `↑→↓←`.
+    OneWay(Direction),
+    /// Walking onto it takes the guard to the other tile of its pair, the cell given here,
+    /// still facing the same way. Drawn as a digit, with both tiles of a pair the same digit.
+    Teleport(usize),
+}
+
+/// Why a map cannot be turned into a lab.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MapError {
+    /// A teleport digit that is not on exactly two tiles of the map.
+    UnpairedTeleport { glyph: char, tiles: usize },
+    /// Row `row`, counted from 0, has `len` cells where the first row has `width`.
+    RaggedRow {
+        row: usize,
+        len: usize,
+        width: usize,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::UnpairedTeleport { glyph, tiles } => write!(
+                f,
+                "Teleport {} needs exactly two tiles but has {}",
+                glyph, tiles
+            ),
+            MapError::RaggedRow { row, len, width } => write!(
+                f,
+                "Row {} is {} cells wide but the first row is {}",
+                row, len, width
+            ),
+        }
+    }
+}
+
+/// The lab floor as a dense grid of cells, indexed row by row.
+#[derive(Debug, Clone)]
+pub struct Lab {
+    width: usize,
+    height: usize,
+    tiles: Vec<Tile>,
+}
+
+impl Lab {
+    pub fn from_matrix(map: &[Vec<char>]) -> Result<Lab, MapError> {
+        let width = map.first().map_or(0, |row| row.len());
+        if let Some((row, cells)) = map.iter().enumerate().find(|(_, r)| r.len() != width) {
+            return Err(MapError::RaggedRow {
+                row,
+                len: cells.len(),
+                width,
+            });
+        }
+        let glyphs: Vec<char> = map.iter().flatten().copied().collect();
+
+        let mut pairs: BTreeMap<char, Vec<usize>> = BTreeMap::new();
+        for (cell, glyph) in glyphs.iter().enumerate() {
+            if glyph.is_ascii_digit() {
+                pairs.entry(*glyph).or_default().push(cell);
+            }
+        }
+        if let Some((&glyph, cells)) = pairs.iter().find(|(_, cells)| cells.len() != 2) {
+            return Err(MapError::UnpairedTeleport {
+                glyph,
+                tiles: cells.len(),
+            });
+        }
+
+        let tiles = glyphs
+            .iter()
+            .enumerate()
+            .map(|(cell, glyph)| match glyph {
+                '#' => Tile::Obstacle,
+                '↑' => Tile::OneWay(Direction::Up),
+                '→' => Tile::OneWay(Direction::Right),
+                '↓' => Tile::OneWay(Direction::Down),
+                '←' => Tile::OneWay(Direction::Left),
+                digit if digit.is_ascii_digit() => {
+                    let pair = &pairs[digit];
+                    Tile::Teleport(if pair[0] == cell { pair[1] } else { pair[0] })
+                }
+                _ => Tile::Floor,
+            })
+            .collect();
+        Ok(Lab {
+            width,
+            height: map.len(),
+            tiles,
+        })
+    }
+
+    /// Width and height in cells.
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn cells(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn index(&self, pos: Vector2) -> Option<usize> {
+        let in_x = (0..self.width as i32).contains(&pos.x);
+        let in_y = (0..self.height as i32).contains(&pos.y);
+        (in_x && in_y).then(|| pos.y as usize * self.width + pos.x as usize)
+    }
+
+    pub fn position(&self, cell: usize) -> Vector2 {
+        Vector2::new((cell % self.width) as i32, (cell / self.width) as i32)
+    }
+
+    pub fn tile(&self, cell: usize) -> Tile {
+        self.tiles[cell]
+    }
+
+    pub fn is_obstacle(&self, cell: usize) -> bool {
+        self.tiles[cell] == Tile::Obstacle
+    }
+
+    /// Puts an obstacle on a floor cell or takes one away, and returns whether it did either.
+    pub fn toggle_obstacle(&mut self, cell: usize) -> bool {
+        self.tiles[cell] = match self.tiles[cell] {
+            Tile::Floor => Tile::Obstacle,
+            Tile::Obstacle => Tile::Floor,
+            _ => return false,
+        };
+        true
+    }
+
+    /// Whether an obstacle can be put on the cell.
+    pub fn is_floor(&self, cell: usize) -> bool {
+        self.tiles[cell] == Tile::Floor
+    }
+
+    /// Whether the cell stops a guard walking onto it in `direction`.
+    pub fn blocks(&self, cell: usize, direction: Direction) -> bool {
+        match self.tiles[cell] {
+            Tile::Obstacle => true,
+            Tile::OneWay(way) => way != direction,
+            _ => false,
+        }
+    }
+
+    /// The other tile of the pair, if the cell is a teleport.
+    pub fn teleport(&self, cell: usize) -> Option<usize> {
+        match self.tiles[cell] {
+            Tile::Teleport(other) => Some(other),
+            _ => None,
+        }
+    }
+
+    /// Whether the lab has nothing but floor and obstacles, which is all the jump table knows
+    /// about.
+    pub fn is_plain(&self) -> bool {
+        self.tiles
+            .iter()
+            .all(|&tile| matches!(tile, Tile::Floor | Tile::Obstacle))
+    }
+
+    /// The cell one step from `cell` in `direction`, if it is on the map.
+    pub fn step(&self, cell: usize, direction: Direction) -> Option<usize> {
+        self.index(self.position(cell).add(&direction.vector()))
+    }
+}
@@ -1,5 +1,19 @@
-use std::fs;
-use std::collections::HashMap;
+mod animate;
+mod jump;
+mod lab;
+mod movement;
+mod render;
+mod tui;
+
+use direction_masks::DirectionMasks;
+use jump::JumpTable;
+use lab::Lab;
+use movement::{Movement, Turn};
+use rayon::prelude::*;
+use render::Trail;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::{env, fs};
 
 #[cfg(feature = "dev")]
 const FILE_NAME: &str = "test.txt";
@@ -14,6 +28,56 @@ enum Direction {
     Left,
 }
 
+impl Direction {
+    /// In clockwise order, matching `index`.
+    const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+
+    fn from_glyph(glyph: char) -> Option<Direction> {
+        match glyph {
+            '^' => Some(Direction::Up),
+            '>' => Some(Direction::Right),
+            'v' => Some(Direction::Down),
+            '<' => Some(Direction::Left),
+            _ => None,
+        }
+    }
+
+    fn glyph(self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::Right => '>',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn turn(self) -> Direction {
+        Direction::ALL[(self.index() + 1) % 4]
+    }
+
+    fn reverse(self) -> Direction {
+        Direction::ALL[(self.index() + 2) % 4]
+    }
+
+    fn vector(self) -> Vector2 {
+        match self {
+            Direction::Up => Vector2::new(0, -1),
+            Direction::Down => Vector2::new(0, 1),
+            Direction::Left => Vector2::new(-1, 0),
+            Direction::Right => Vector2::new(1, 0),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 struct Vector2 {
     x: i32,
@@ -24,7 +88,7 @@ impl Vector2 {
     fn new(x: i32, y: i32) -> Self {
         Self { x, y }
     }
-    
+
     fn add(&self, other: &Vector2) -> Self {
         Self {
             x: self.x + other.x,
@@ -33,65 +97,39 @@ impl Vector2 {
     }
 }
 
+#[derive(Copy, Clone)]
 struct Guard {
     direction: Direction,
     position: Vector2,
-    path: HashMap<Vector2, Vec<Direction>>
+    /// Number of turns so far, for turn rules that change from one turn to the next.
+    turns: usize,
 }
 
 impl Guard {
     fn new(position: Vector2, direction: Direction) -> Self {
         Self {
             position,
-            direction, 
-            path: HashMap::from([(position,vec![direction])])
+            direction,
+            turns: 0,
         }
     }
 
-    fn get_direction(&mut self) -> Vector2 {
-        let direction = match &self.direction {
-            Direction::Up => Vector2::new(0, -1),
-            Direction::Down => Vector2::new(0, 1),
-            Direction::Left => Vector2::new(-1, 0),
-            Direction::Right => Vector2::new(1, 0),
-        };
-        direction.clone()
-    }
-
-    fn next_step(&mut self) -> Vector2 {
-        Vector2::new(self.position.x,self.position.y).add(&self.get_direction())
-    }
-
-    fn move_step(&mut self) -> () {
-        let movement = self.get_direction();
-        self.position = self.position.add(&movement);
-    }
-
-    fn repeated_pos_and_dir(&mut self, key: &Vector2, value: &Direction) -> bool {
-        if let Some(values) = self.path.get(key) {
-            values.contains(value)
-        } else {
-            false
-        }
+    fn next_step(&self, movement: &Movement) -> Vector2 {
+        self.position.add(&movement.vector(self.direction))
     }
 
-    fn add_to_path(&mut self, key: &Vector2, value: &Direction) -> () {
-        self.path.entry(*key).or_insert_with(Vec::new).push(*value);
+    fn move_step(&mut self, movement: &Movement) {
+        self.position = self.next_step(movement);
     }
 
-    fn turn(&mut self) -> () {
-        let new_dir = match self.direction {
-            Direction::Up => Direction::Right,
-            Direction::Right => Direction::Down,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
-        };
-        self.direction = new_dir;
+    fn turn(&mut self, movement: &Movement) {
+        self.direction = movement.turn.apply(self.direction, self.turns);
+        self.turns += 1;
     }
 }
 
 fn read_matrix(path: &str) -> Vec<Vec<char>> {
-    let file = fs::read_to_string(path).expect("Could not read file"); 
+    let file = fs::read_to_string(path).expect("Could not read file");
     let mut matrix: Vec<Vec<char>> = Vec::new();
     for line in file.lines() {
         matrix.push(line.chars().collect());
@@ -99,85 +137,636 @@ fn read_matrix(path: &str) -> Vec<Vec<char>> {
     matrix
 }
 
-fn get_start_pos(map: &Vec<Vec<char>>) -> Vector2 {
-    let mut start_pos = Vector2::new(0,0);
-    for (i,row) in map.iter().enumerate() {
+/// Every guard on the map, in reading order, facing the way its glyph points.
+fn get_guards(map: &[Vec<char>]) -> Vec<Guard> {
+    let mut guards = Vec::new();
+    for (i, row) in map.iter().enumerate() {
         for (j, val) in row.iter().enumerate() {
-            if *val == '^' {
-                start_pos = Vector2::new(j as i32 ,i as i32)
+            if let Some(direction) = Direction::from_glyph(*val) {
+                guards.push(Guard::new(Vector2::new(j as i32, i as i32), direction));
             }
         }
     }
-    start_pos 
+    guards
 }
 
-fn in_bounds(x: i32, y: i32, x_lim: i32,y_lim:i32) -> bool {
-    let x_in_bound =  (x >= 0) && (x < x_lim);
-    let y_in_bound =  (y >= 0) && (y < y_lim);
-    x_in_bound && y_in_bound
+/// The cells a guard visits on its patrol.
+struct Patrol {
+    start: usize,
+    visited: Vec<bool>,
+    /// Whether the guard walks in a loop instead of leaving the lab.
+    loops: bool,
+    trail: Trail,
+    /// For every visited cell but the start, the guard right before it first stepped onto it.
+    entered_from: Vec<Option<Guard>>,
 }
 
-fn walk_map_checking_for_loops(guard: &mut Guard, map: &Vec<Vec<char>>) -> bool {
-    let x_lim = map.len() as i32;
-    let y_lim = map[0].len() as i32;
-    let mut next_pos = guard.next_step();
-    
-    while in_bounds(next_pos.x, next_pos.y, x_lim, y_lim) {
-        log::debug!("before move: x: {}, y: {}, n_steps: {}",guard.position.x, guard.position.y, guard.path.keys().len());
-        if map[next_pos.y as usize][next_pos.x as usize] == '#' {
-            guard.turn();
-            next_pos = guard.next_step();
-            log::debug!("TURN! object at x: {}, y: {}", next_pos.x, next_pos.y,);
-            continue
-        }
-        guard.move_step();
+impl Patrol {
+    fn count(&self) -> usize {
+        self.visited.iter().filter(|&&v| v).count()
+    }
+}
+
+/// Moves the guard one step ahead, or turns it if the way is blocked, and returns its new cell
+/// or `None` if it walked off the map. `on_move` is called with the guard right before a step
+/// and the cell it steps onto, and once more for the other end of a teleport.
+fn advance(
+    guard: &mut Guard,
+    lab: &Lab,
+    movement: &Movement,
+    extra: Option<usize>,
+    on_move: &mut impl FnMut(&Guard, usize),
+) -> Option<usize> {
+    let next = lab.index(guard.next_step(movement))?;
+    if lab.blocks(next, guard.direction) || extra == Some(next) {
+        let obstacle = guard.next_step(movement);
+        guard.turn(movement);
+        log::debug!("TURN! object at x: {}, y: {}", obstacle.x, obstacle.y);
+        return lab.index(guard.position);
+    }
+    on_move(guard, next);
+    guard.move_step(movement);
+    let Some(other) = lab.teleport(next) else {
+        return Some(next);
+    };
+    on_move(guard, other);
+    guard.position = lab.position(other);
+    Some(other)
+}
 
-        let direction = guard.direction;
-        let position = guard.position.clone();
-        if guard.repeated_pos_and_dir(&position, &direction) {
-            return true
+/// Where the guard's state is kept in `DirectionMasks`. The same cell and direction at another phase
+/// of the turn rule is another state, so the set needs room for every phase.
+fn state_slot(lab: &Lab, movement: &Movement, cell: usize, guard: &Guard) -> usize {
+    movement.phase(guard.turns) * lab.cells() + cell
+}
+
+/// Walks the guard one step at a time until it leaves the lab or comes back to a state it was
+/// in before, and returns whether it is stuck in a loop. Unlike the jump table this works with
+/// any movement rules and tiles. A guard stuck in a loop is left in the state it came back to.
+///
+/// `states` is cleared before use.
+fn walk(
+    guard: &mut Guard,
+    lab: &Lab,
+    movement: &Movement,
+    extra: Option<usize>,
+    states: &mut DirectionMasks,
+    mut on_move: impl FnMut(&Guard, usize),
+) -> bool {
+    let cell = lab.index(guard.position).expect("Guard is not on the map");
+    states.clear();
+    states.insert(
+        state_slot(lab, movement, cell, guard),
+        guard.direction.index(),
+    );
+
+    while let Some(cell) = advance(guard, lab, movement, extra, &mut on_move) {
+        if !states.insert(
+            state_slot(lab, movement, cell, guard),
+            guard.direction.index(),
+        ) {
+            return true;
         }
-        guard.add_to_path(&position, &direction);
-        next_pos = guard.next_step();
-        log::debug!("after move: x: {}, y: {}",guard.position.x, guard.position.y);
     }
+    false
+}
 
-    return false
+/// Walks the guard until it leaves the lab, recording the cells it visits. If it gets stuck in
+/// a loop instead, the walk stops when it is back in a known state.
+fn walk_map(guard: &mut Guard, lab: &Lab, movement: &Movement) -> Patrol {
+    walk_map_with_obstacle(guard, lab, movement, None)
 }
 
-fn add_obstacles(map: &mut Vec<Vec<char>>, start_pos: Vector2, start_dir: Direction) -> i32 {
-    let mut loops = 0;
-    for i in 0..map.len() {
-        for j in 0..map[i].len() {
-            let mut guard = Guard::new(start_pos,start_dir);
-            if map[i][j] == '#' || ((start_pos.x ,start_pos.y ) == (j as i32, i as i32)) {
-                continue;
-            }
-            map[i][j] = '#';
-            if walk_map_checking_for_loops(&mut guard, &map) {
-                loops += 1;
-            }
-            map[i][j] = '.';
+/// Like `walk_map`, with one more obstacle in the lab.
+fn walk_map_with_obstacle(
+    guard: &mut Guard,
+    lab: &Lab,
+    movement: &Movement,
+    extra: Option<usize>,
+) -> Patrol {
+    let start = lab.index(guard.position).expect("Guard is not on the map");
+    let mut visited = vec![false; lab.cells()];
+    let mut entered_from = vec![None; lab.cells()];
+    let mut trail = Trail::new(lab.cells());
+    visited[start] = true;
+
+    let mut states = DirectionMasks::new(lab.cells() * movement.turn.period());
+    let loops = walk(guard, lab, movement, extra, &mut states, |from, cell| {
+        trail.add(lab.index(from.position).unwrap(), from.direction);
+        trail.add(cell, from.direction);
+        if !visited[cell] {
+            visited[cell] = true;
+            entered_from[cell] = Some(*from);
         }
+    });
+    if loops {
+        log::debug!("Guard is stuck in a loop");
+    }
+    Patrol {
+        start,
+        visited,
+        loops,
+        trail,
+        entered_from,
     }
-    loops
+}
+
+/// Finds the floor cells where one more obstacle makes the guard walk in a loop, in cell order.
+/// An obstacle off the guard's patrol is never run into, so only the patrol's cells are tried.
+/// The walk with the new obstacle is the same as the patrol until the guard first gets to it,
+/// so each walk starts right in front of the obstacle.
+///
+/// With the puzzle's own rules on a plain lab the walks use the jump table, otherwise they go
+/// one step at a time. Cells are tried in parallel on the current rayon pool. Every rayon job
+/// gets its own visited set, which it clears and reuses for all cells it tries.
+fn add_obstacles(lab: &Lab, patrol: &Patrol, movement: &Movement) -> Vec<usize> {
+    let table = (movement.is_standard() && lab.is_plain()).then(|| JumpTable::new(lab));
+    let candidates: Vec<(usize, Guard)> = patrol
+        .entered_from
+        .iter()
+        .enumerate()
+        .filter(|&(cell, _)| cell != patrol.start && lab.is_floor(cell))
+        .filter_map(|(cell, from)| from.map(|from| (cell, from)))
+        .collect();
+    candidates
+        .par_iter()
+        .map_init(
+            || DirectionMasks::new(lab.cells() * movement.turn.period()),
+            |visited, &(cell, mut from)| {
+                let loops = match &table {
+                    Some(table) => {
+                        let from_cell = lab.index(from.position).unwrap();
+                        table.loops(from_cell, from.direction, Some(cell), visited)
+                    }
+                    None => walk(&mut from, lab, movement, Some(cell), visited, |_, _| {}),
+                };
+                loops.then_some(cell)
+            },
+        )
+        .flatten()
+        .collect()
+}
+
+/// An obstacle that makes the guard walk in a loop, and the loop as the cell and direction of
+/// the guard after every step or turn, starting from the first state it comes back to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Loop {
+    obstacle: usize,
+    cycle: Vec<(usize, Direction)>,
+}
+
+/// Finds every loop one more obstacle can make, in the order of the obstacles' cells.
+fn find_loops(lab: &Lab, patrol: &Patrol, movement: &Movement) -> Vec<Loop> {
+    add_obstacles(lab, patrol, movement)
+        .par_iter()
+        .map_init(
+            || DirectionMasks::new(lab.cells() * movement.turn.period()),
+            |visited, &obstacle| {
+                let mut guard = patrol.entered_from[obstacle].unwrap();
+                walk(
+                    &mut guard,
+                    lab,
+                    movement,
+                    Some(obstacle),
+                    visited,
+                    |_, _| {},
+                );
+                let state = |guard: &Guard| {
+                    let phase = movement.phase(guard.turns);
+                    (guard.position, guard.direction, phase)
+                };
+
+                let start = state(&guard);
+                let mut cycle = vec![(lab.index(guard.position).unwrap(), guard.direction)];
+                while let Some(cell) =
+                    advance(&mut guard, lab, movement, Some(obstacle), &mut |_, _| {})
+                {
+                    if state(&guard) == start {
+                        break;
+                    }
+                    cycle.push((cell, guard.direction));
+                }
+                Loop { obstacle, cycle }
+            },
+        )
+        .collect()
 }
 
 fn main() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let mut lab_map = read_matrix(FILE_NAME);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(i) = args.iter().position(|a| a == "--threads") {
+        let threads: usize = args
+            .get(i + 1)
+            .and_then(|n| n.parse().ok())
+            .expect("--threads needs a number");
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .expect("Could not build thread pool");
+    }
+    let turn = match args.iter().position(|a| a == "--turn") {
+        Some(i) => args
+            .get(i + 1)
+            .and_then(|rule| Turn::parse(rule))
+            .expect("--turn needs right, left, reverse, alternate or a table like >v<^"),
+        None => Turn::Right,
+    };
+    let movement = Movement {
+        turn,
+        diagonal: args.iter().any(|a| a == "--diagonal"),
+    };
+    log::debug!("Movement rules: {:?}", movement);
+    // `--render` draws the patrols with every obstacle that makes a loop, and `--loops` lists
+    // the loops and draws the patrol for each of them
+    let render = args.iter().any(|a| a == "--render");
+    let show_loops = args.iter().any(|a| a == "--loops");
+    // `--animate FILE` writes the patrols as a GIF, or as numbered PNG or PPM frames, with
+    // `--cell-size`, `--steps-per-frame` and `--frame-delay` (in milliseconds) to tune it
+    let option = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+    };
+    let number = |name: &str| {
+        option(name).map(|n| {
+            n.parse()
+                .unwrap_or_else(|_| panic!("{} needs a number", name))
+        })
+    };
+    let animation = option("--animate").map(|path| {
+        let defaults = animate::Options::default();
+        let options = animate::Options {
+            cell_size: number("--cell-size").unwrap_or(defaults.cell_size),
+            steps_per_frame: number("--steps-per-frame").unwrap_or(defaults.steps_per_frame),
+            frame_delay: number("--frame-delay").map_or(defaults.frame_delay, |d: usize| {
+                u16::try_from(d)
+                    .unwrap_or_else(|_| panic!("--frame-delay can be at most {} ms", u16::MAX))
+            }),
+        };
+        (path, options)
+    });
+
+    let lab_map = read_matrix(FILE_NAME);
     log::debug!("Loaded lab_map: {:?}", lab_map);
+    let lab = match Lab::from_matrix(&lab_map) {
+        Ok(lab) => lab,
+        Err(e) => {
+            log::error!("{}", e);
+            return;
+        }
+    };
+
+    // `--tui` steps through the first guard's patrol in the terminal instead
+    if args.iter().any(|a| a == "--tui") {
+        if get_guards(&lab_map).is_empty() {
+            log::error!("There is no guard on the map");
+            return;
+        }
+        tui::run(&lab_map, lab, movement).expect("Could not run the terminal stepper");
+        return;
+    }
+
+    let guards = get_guards(&lab_map);
+    let starts: Vec<usize> = guards
+        .iter()
+        .filter_map(|guard| lab.index(guard.position))
+        .collect();
+
+    let mut visited = vec![false; lab.cells()];
+    let mut trail = Trail::new(lab.cells());
+    let mut moves: Vec<animate::Move> = Vec::new();
+    let mut any_loops = false;
+    let mut obstacles = BTreeSet::new();
+    for (i, mut guard) in guards.into_iter().enumerate() {
+        let start = guard;
+        log::debug!(
+            "Guard {} starts at x: {}, y: {}, facing {:?}",
+            i,
+            guard.position.x,
+            guard.position.y,
+            guard.direction
+        );
+        let patrol = walk_map(&mut guard, &lab, &movement);
+        log::info!(
+            "Guard {} visits {} distinct positions{}",
+            i,
+            patrol.count(),
+            if patrol.loops {
+                " walking in a loop"
+            } else {
+                ""
+            }
+        );
+        for (cell, v) in patrol.visited.iter().enumerate() {
+            visited[cell] |= v;
+        }
+        trail.merge(&patrol.trail);
+        any_loops |= patrol.loops;
+        if animation.is_some() {
+            let mut guard = start;
+            let mut states = DirectionMasks::new(lab.cells() * movement.turn.period());
+            walk(
+                &mut guard,
+                &lab,
+                &movement,
+                None,
+                &mut states,
+                |from, cell| {
+                    moves.push((lab.index(from.position).unwrap(), cell, from.direction));
+                },
+            );
+        }
 
-    let start_pos = get_start_pos(&lab_map);
-    log::debug!("start x: {}, start y: {}", start_pos.x, start_pos.y);
+        if !show_loops {
+            obstacles.extend(add_obstacles(&lab, &patrol, &movement));
+            continue;
+        }
+        for found in find_loops(&lab, &patrol, &movement) {
+            if starts.contains(&found.obstacle) {
+                continue;
+            }
+            let at = lab.position(found.obstacle);
+            log::info!(
+                "Guard {} loops through {} states with an obstacle at x: {}, y: {}",
+                i,
+                found.cycle.len(),
+                at.x,
+                at.y
+            );
+            let mut guard = start;
+            let with_obstacle =
+                walk_map_with_obstacle(&mut guard, &lab, &movement, Some(found.obstacle));
+            println!(
+                "{}",
+                render::draw(&lab_map, &with_obstacle.trail, &[found.obstacle])
+            );
+            obstacles.insert(found.obstacle);
+        }
+    }
+    obstacles.retain(|cell| !starts.contains(cell));
+
+    if let Some((path, options)) = animation {
+        match animate::export(Path::new(path), &lab, &moves, &options) {
+            Ok(frames) => log::info!("Wrote {} frames of animation to {}", frames, path),
+            Err(e) => log::error!("Could not write animation: {}", e),
+        }
+    }
+    if render {
+        let obstacles: Vec<usize> = obstacles.iter().copied().collect();
+        print!("{}", render::draw(&lab_map, &trail, &obstacles));
+    }
 
-    let mut guard = Guard::new(start_pos,Direction::Up);
+    log::info!(
+        "Number of distinct positions was: {}",
+        visited.iter().filter(|&&v| v).count()
+    );
+    log::info!("Any guard walks in a loop: {}", any_loops);
+    log::info!(
+        "Number of loops creatable with one more obstacle was: {}",
+        obstacles.len()
+    );
+}
 
-    walk_map_checking_for_loops(&mut guard, &lab_map);
-    log::info!("Number of distinct positions was: {}", guard.path.keys().len());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let n_loops = add_obstacles(&mut lab_map, start_pos, Direction::Up);
-    log::info!("Number of loops creatable with one more obstacle was: {}", n_loops);
+    #[test]
+    fn test_patrol_and_obstacles() {
+        let map = read_matrix("test.txt");
+        let lab = Lab::from_matrix(&map).unwrap();
+        let mut guard = get_guards(&map).remove(0);
+        let patrol = walk_map(&mut guard, &lab, &Movement::default());
+        assert_eq!(patrol.count(), 41);
+        assert_eq!(add_obstacles(&lab, &patrol, &Movement::default()).len(), 6);
+    }
+
+    #[test]
+    fn test_add_obstacles_is_deterministic() {
+        let map = read_matrix("input.txt");
+        let lab = Lab::from_matrix(&map).unwrap();
+        let mut guard = get_guards(&map).remove(0);
+        let patrol = walk_map(&mut guard, &lab, &Movement::default());
+        let with_threads = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| add_obstacles(&lab, &patrol, &Movement::default()))
+        };
+        let sequential = with_threads(1);
+        assert!(sequential.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(with_threads(4), sequential);
+    }
 
+    #[test]
+    fn test_several_guards() {
+        let map: Vec<Vec<char>> = ["#....", "..>.#", "<....", ".#.^.", "...#v"]
+            .iter()
+            .map(|r| r.chars().collect())
+            .collect();
+        let lab = Lab::from_matrix(&map).unwrap();
+        let guards = get_guards(&map);
+        let directions: Vec<Direction> = guards.iter().map(|g| g.direction).collect();
+        assert_eq!(
+            directions,
+            vec![
+                Direction::Right,
+                Direction::Left,
+                Direction::Up,
+                Direction::Down
+            ]
+        );
+        let counts: Vec<usize> = guards
+            .into_iter()
+            .map(|mut guard| walk_map(&mut guard, &lab, &Movement::default()).count())
+            .collect();
+        assert_eq!(counts, vec![7, 1, 4, 1]);
+    }
+
+    #[test]
+    fn test_guard_in_a_loop() {
+        let map: Vec<Vec<char>> = [".#.v", "...#", "#<..", "..#."]
+            .iter()
+            .map(|r| r.chars().collect())
+            .collect();
+        let lab = Lab::from_matrix(&map).unwrap();
+        let patrols: Vec<Patrol> = get_guards(&map)
+            .into_iter()
+            .map(|mut guard| walk_map(&mut guard, &lab, &Movement::default()))
+            .collect();
+        assert_eq!((patrols[0].count(), patrols[0].loops), (2, false));
+        assert_eq!((patrols[1].count(), patrols[1].loops), (4, true));
+    }
+
+    fn matrix(rows: &[&str]) -> Vec<Vec<char>> {
+        rows.iter().map(|r| r.chars().collect()).collect()
+    }
+
+    #[test]
+    fn test_step_by_step_matches_jump_table() {
+        let map = read_matrix("test.txt");
+        let lab = Lab::from_matrix(&map).unwrap();
+        let movement = Movement::default();
+        let patrol = walk_map(&mut get_guards(&map).remove(0), &lab, &movement);
+        let mut states = DirectionMasks::new(lab.cells());
+        let looping: Vec<usize> = (0..lab.cells())
+            .filter(|&cell| lab.is_floor(cell) && cell != patrol.start)
+            .filter(|&cell| {
+                let mut guard = get_guards(&map).remove(0);
+                walk(
+                    &mut guard,
+                    &lab,
+                    &movement,
+                    Some(cell),
+                    &mut states,
+                    |_, _| {},
+                )
+            })
+            .collect();
+        assert_eq!(looping, add_obstacles(&lab, &patrol, &movement));
+    }
+
+    #[test]
+    fn test_turning_left() {
+        // Mirrored left to right, the example walks the same way turning left
+        let map: Vec<Vec<char>> = read_matrix("test.txt")
+            .into_iter()
+            .map(|row| row.into_iter().rev().collect())
+            .collect();
+        let lab = Lab::from_matrix(&map).unwrap();
+        let movement = Movement {
+            turn: Turn::Left,
+            diagonal: false,
+        };
+        let patrol = walk_map(&mut get_guards(&map).remove(0), &lab, &movement);
+        assert_eq!(patrol.count(), 41);
+        assert_eq!(add_obstacles(&lab, &patrol, &movement).len(), 6);
+    }
+
+    #[test]
+    fn test_diagonal_movement() {
+        let map = matrix(&["..#.", ".^..", "....", "...."]);
+        let lab = Lab::from_matrix(&map).unwrap();
+        let movement = Movement {
+            turn: Turn::Right,
+            diagonal: true,
+        };
+        let patrol = walk_map(&mut get_guards(&map).remove(0), &lab, &movement);
+        let visited: Vec<usize> = (0..lab.cells()).filter(|&c| patrol.visited[c]).collect();
+        let expected: Vec<usize> = [(1, 1), (2, 2), (3, 3)]
+            .iter()
+            .map(|&(x, y)| lab.index(Vector2::new(x, y)).unwrap())
+            .collect();
+        assert_eq!(visited, expected);
+    }
+
+    #[test]
+    fn test_teleports_and_one_way_tiles() {
+        let movement = Movement::default();
+        let walk = |rows: &[&str]| {
+            let map = matrix(rows);
+            let lab = Lab::from_matrix(&map).unwrap();
+            let patrol = walk_map(&mut get_guards(&map).remove(0), &lab, &movement);
+            (patrol.count(), patrol.loops)
+        };
+        assert_eq!(walk(&[".1..", "....", ".^.1"]), (6, false));
+        // The guard comes out of the teleport facing up, into the wrong end of the arrow
+        assert_eq!(walk(&[".1..", "...↓", ".^.1"]), (4, false));
+        assert_eq!(walk(&[".1..", "...↑", ".^.1"]), (6, false));
+        // Every time the guard walks up it is taken back down
+        assert_eq!(walk(&[".1.", "...", ".^.", ".1."]), (4, true));
+
+        let unpaired = Lab::from_matrix(&matrix(&[".1.", ".^.", ".1.", "..1"])).unwrap_err();
+        assert_eq!(
+            unpaired.to_string(),
+            "Teleport 1 needs exactly two tiles but has 3"
+        );
+        assert!(Lab::from_matrix(&matrix(&["2^."])).is_err());
+
+        // A guard past the end of the first row would not be on the lab
+        let ragged = Lab::from_matrix(&matrix(&["...", "....^", "..."])).unwrap_err();
+        assert_eq!(
+            ragged,
+            lab::MapError::RaggedRow {
+                row: 1,
+                len: 5,
+                width: 3
+            }
+        );
+        assert!(Lab::from_matrix(&matrix(&["..^.", ".."])).is_err());
+    }
+
+    #[test]
+    fn test_alternating_turns() {
+        let movement = Movement {
+            turn: Turn::Alternate,
+            diagonal: false,
+        };
+        let map = matrix(&[".#.", "#^#", ".#."]);
+        let lab = Lab::from_matrix(&map).unwrap();
+        let patrol = walk_map(&mut get_guards(&map).remove(0), &lab, &movement);
+        assert_eq!((patrol.count(), patrol.loops), (1, true));
+    }
+
+    #[test]
+    fn test_find_loops() {
+        let map = read_matrix("test.txt");
+        let lab = Lab::from_matrix(&map).unwrap();
+        let movement = Movement::default();
+        let start = get_guards(&map).remove(0);
+        let patrol = walk_map(&mut start.clone(), &lab, &movement);
+        let loops = find_loops(&lab, &patrol, &movement);
+        let obstacles: Vec<usize> = loops.iter().map(|l| l.obstacle).collect();
+        assert_eq!(obstacles, add_obstacles(&lab, &patrol, &movement));
+
+        // Every cycle is closed, and the guard turns in front of the obstacle on it
+        let mut states = DirectionMasks::new(lab.cells());
+        for found in &loops {
+            let (cell, direction) = found.cycle[0];
+            let mut guard = Guard::new(lab.position(cell), direction);
+            guard.turns = patrol.entered_from[found.obstacle].unwrap().turns;
+            assert!(walk(
+                &mut guard,
+                &lab,
+                &movement,
+                Some(found.obstacle),
+                &mut states,
+                |_, _| {}
+            ));
+            assert_eq!(
+                (lab.index(guard.position).unwrap(), guard.direction),
+                found.cycle[0]
+            );
+            assert!(found
+                .cycle
+                .iter()
+                .any(|&(cell, d)| lab.step(cell, d) == Some(found.obstacle)));
+        }
+
+        // The fourth of the puzzle's examples
+        let found = &loops[1];
+        assert_eq!(lab.position(found.obstacle), Vector2::new(6, 7));
+        assert_eq!(found.cycle.len(), 16);
+        let with_obstacle =
+            walk_map_with_obstacle(&mut start.clone(), &lab, &movement, Some(found.obstacle));
+        let expected = [
+            "....#.....",
+            "....+---+#",
+            "....|...|.",
+            "..#.|...|.",
+            "..+-+-+#|.",
+            "..|.|.|.|.",
+            ".#+-^-+-+.",
+            "......O.#.",
+            "#.........",
+            "......#...",
+        ];
+        assert_eq!(
+            render::draw(&map, &with_obstacle.trail, &[found.obstacle]),
+            expected.join("\n") + "\n"
+        );
+    }
 }
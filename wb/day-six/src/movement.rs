@@ -0,0 +1,136 @@
+use crate::{Direction, Vector2};
+
+/// How the guard turns when it runs into an obstacle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Turn {
+    Right,
+    Left,
+    Reverse,
+    /// Right on the first turn, left on the second, and so on.
+    Alternate,
+    /// The new direction for every direction, in `Direction::ALL` order.
+    Table([Direction; 4]),
+}
+
+impl Turn {
+    /// Reads `right`, `left`, `reverse`, `alternate`, or a turn table as four of `^>v<` giving
+    /// the new direction when facing up, right, down and left. `>v<^` is the same as `right`.
+    pub fn parse(rule: &str) -> Option<Turn> {
+        match rule {
+            "right" => Some(Turn::Right),
+            "left" => Some(Turn::Left),
+            "reverse" => Some(Turn::Reverse),
+            "alternate" => Some(Turn::Alternate),
+            table => {
+                let directions: Vec<Direction> = table
+                    .chars()
+                    .map(Direction::from_glyph)
+                    .collect::<Option<_>>()?;
+                directions.try_into().ok().map(Turn::Table)
+            }
+        }
+    }
+
+    /// The guard's new direction, given how many turns it made before this one.
+    pub fn apply(self, direction: Direction, turns: usize) -> Direction {
+        match self {
+            Turn::Right => direction.turn(),
+            Turn::Left => direction.reverse().turn(),
+            Turn::Reverse => direction.reverse(),
+            Turn::Alternate => match turns % 2 {
+                0 => direction.turn(),
+                _ => direction.reverse().turn(),
+            },
+            Turn::Table(table) => table[direction.index()],
+        }
+    }
+
+    /// Number of turns after which the rule repeats itself.
+    pub fn period(self) -> usize {
+        match self {
+            Turn::Alternate => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// The rules the guard follows on patrol.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Movement {
+    pub turn: Turn,
+    /// Whether the guard walks diagonally, each direction turned halfway to the next one
+    /// clockwise, so facing up it walks up and to the right.
+    pub diagonal: bool,
+}
+
+impl Default for Movement {
+    fn default() -> Movement {
+        Movement {
+            turn: Turn::Right,
+            diagonal: false,
+        }
+    }
+}
+
+impl Movement {
+    /// Whether these are the puzzle's own rules, which the jump table is built for.
+    pub fn is_standard(&self) -> bool {
+        *self == Movement::default()
+    }
+
+    /// One step in `direction`.
+    pub fn vector(&self, direction: Direction) -> Vector2 {
+        if self.diagonal {
+            direction.vector().add(&direction.turn().vector())
+        } else {
+            direction.vector()
+        }
+    }
+
+    /// Where the guard is in the cycle of its turn rule after `turns` turns. Two walks can
+    /// only be in the same state if they are at the same point of the cycle.
+    pub fn phase(&self, turns: usize) -> usize {
+        turns % self.turn.period()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turns() {
+        assert_eq!(Turn::parse("right"), Some(Turn::Right));
+        assert_eq!(Turn::parse("^^^"), None);
+        assert_eq!(Turn::parse("up"), None);
+
+        let table = Turn::parse("v<^>").unwrap();
+        for direction in Direction::ALL {
+            assert_eq!(
+                Turn::parse(">v<^").unwrap().apply(direction, 0),
+                direction.turn()
+            );
+            assert_eq!(table.apply(direction, 0), Turn::Reverse.apply(direction, 0));
+            assert_eq!(
+                Turn::Left.apply(Turn::Right.apply(direction, 0), 1),
+                direction
+            );
+        }
+
+        let alternate: Vec<Direction> = (0..4)
+            .scan(Direction::Up, |d, turns| {
+                *d = Turn::Alternate.apply(*d, turns);
+                Some(*d)
+            })
+            .collect();
+        assert_eq!(
+            alternate,
+            vec![
+                Direction::Right,
+                Direction::Up,
+                Direction::Right,
+                Direction::Up
+            ]
+        );
+    }
+}
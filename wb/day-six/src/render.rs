@@ -0,0 +1,63 @@
+use crate::Direction;
+use direction_masks::DirectionMasks;
+
+/// For every cell, the directions the guard walked through it, one bit per direction.
+#[derive(Debug, Clone)]
+pub struct Trail {
+    masks: DirectionMasks,
+}
+
+impl Trail {
+    pub fn new(cells: usize) -> Trail {
+        Trail {
+            masks: DirectionMasks::new(cells),
+        }
+    }
+
+    pub fn add(&mut self, cell: usize, direction: Direction) {
+        self.masks.insert(cell, direction.index());
+    }
+
+    /// The directions walked through the cell, bit `d` set for `Direction::ALL[d]`.
+    pub fn mask(&self, cell: usize) -> u8 {
+        self.masks.mask(cell)
+    }
+
+    pub fn merge(&mut self, other: &Trail) {
+        self.masks.union_with(&other.masks);
+    }
+
+    /// How the puzzle draws the cell: `|` when walked up or down, `-` when walked left or
+    /// right, and `+` when both.
+    pub fn glyph(&self, cell: usize) -> Option<char> {
+        let vertical = 1 << Direction::Up.index() | 1 << Direction::Down.index();
+        let mask = self.mask(cell);
+        match (mask & vertical != 0, mask & !vertical != 0) {
+            (true, true) => Some('+'),
+            (true, false) => Some('|'),
+            (false, true) => Some('-'),
+            (false, false) => None,
+        }
+    }
+}
+
+/// Draws the map like the puzzle does, with the trail over the floor and the given cells as
+/// `O`. Everything else is drawn as on the map.
+pub fn draw(map: &[Vec<char>], trail: &Trail, obstacles: &[usize]) -> String {
+    let width = map.first().map_or(0, |row| row.len());
+    let mut out = String::new();
+    for (y, row) in map.iter().enumerate() {
+        for (x, &c) in row.iter().enumerate() {
+            let cell = y * width + x;
+            if obstacles.contains(&cell) {
+                out.push('O');
+            } else if c == '.' {
+                out.push(trail.glyph(cell).unwrap_or('.'));
+            } else {
+                out.push(c);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,335 @@
+use crate::lab::{Lab, Tile};
+use crate::movement::Movement;
+use crate::render::Trail;
+use crate::{add_obstacles, advance, get_guards, state_slot, walk_map, Guard, Vector2};
+use direction_masks::DirectionMasks;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+
+const HELP: &str = "space/s step, t run to turn, e run to end, r reset, \
+    arrows/hjkl move cursor, o toggle obstacle, q quit";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Status {
+    Walking,
+    LeftTheLab,
+    Looping,
+}
+
+/// A guard's patrol, walked a step at a time on a lab that can be changed along the way.
+struct Stepper<'a> {
+    map: &'a [Vec<char>],
+    lab: Lab,
+    movement: Movement,
+    start: Guard,
+    guard: Guard,
+    trail: Trail,
+    visited: Vec<bool>,
+    states: DirectionMasks,
+    steps: usize,
+    status: Status,
+    cursor: Vector2,
+    /// Whether the whole patrol from the start loops, checked again after every change to the
+    /// lab.
+    loops: bool,
+    /// Number of cells where one more obstacle would make the patrol loop.
+    loop_obstacles: usize,
+}
+
+impl<'a> Stepper<'a> {
+    /// Starts at the first guard of `map`, which has to have one.
+    fn new(map: &'a [Vec<char>], lab: Lab, movement: Movement) -> Stepper<'a> {
+        let start = *get_guards(map)
+            .first()
+            .expect("There is no guard on the map");
+        let mut stepper = Stepper {
+            map,
+            trail: Trail::new(lab.cells()),
+            visited: vec![false; lab.cells()],
+            states: DirectionMasks::new(lab.cells() * movement.turn.period()),
+            lab,
+            movement,
+            start,
+            guard: start,
+            steps: 0,
+            status: Status::Walking,
+            cursor: start.position,
+            loops: false,
+            loop_obstacles: 0,
+        };
+        stepper.reset();
+        stepper.check_loops();
+        stepper
+    }
+
+    fn reset(&mut self) {
+        self.guard = self.start;
+        self.trail = Trail::new(self.lab.cells());
+        self.visited.fill(false);
+        self.states.clear();
+        self.steps = 0;
+        self.status = Status::Walking;
+
+        let cell = self.lab.index(self.guard.position).unwrap();
+        self.visited[cell] = true;
+        let slot = state_slot(&self.lab, &self.movement, cell, &self.guard);
+        self.states.insert(slot, self.guard.direction.index());
+    }
+
+    /// Walks the whole patrol again to see if it loops, and tries every obstacle on it.
+    fn check_loops(&mut self) {
+        let patrol = walk_map(&mut self.start.clone(), &self.lab, &self.movement);
+        self.loops = patrol.loops;
+        self.loop_obstacles = add_obstacles(&self.lab, &patrol, &self.movement).len();
+    }
+
+    /// Moves or turns the guard once, unless the patrol is over.
+    fn step(&mut self) {
+        if self.status != Status::Walking {
+            return;
+        }
+        let Stepper {
+            lab,
+            movement,
+            guard,
+            trail,
+            visited,
+            ..
+        } = self;
+        let next = advance(guard, lab, movement, None, &mut |from, cell| {
+            trail.add(lab.index(from.position).unwrap(), from.direction);
+            trail.add(cell, from.direction);
+            visited[cell] = true;
+        });
+        self.steps += 1;
+        self.status = match next {
+            None => Status::LeftTheLab,
+            Some(cell) => {
+                let slot = state_slot(&self.lab, &self.movement, cell, &self.guard);
+                if self.states.insert(slot, self.guard.direction.index()) {
+                    Status::Walking
+                } else {
+                    Status::Looping
+                }
+            }
+        };
+    }
+
+    fn run_to_turn(&mut self) {
+        let turns = self.guard.turns;
+        while self.status == Status::Walking && self.guard.turns == turns {
+            self.step();
+        }
+    }
+
+    fn run_to_end(&mut self) {
+        while self.status == Status::Walking {
+            self.step();
+        }
+    }
+
+    fn move_cursor(&mut self, dx: i32, dy: i32) {
+        let moved = Vector2::new(self.cursor.x + dx, self.cursor.y + dy);
+        if self.lab.index(moved).is_some() {
+            self.cursor = moved;
+        }
+    }
+
+    /// Puts an obstacle at the cursor or takes it away, then starts the patrol over.
+    fn toggle_obstacle(&mut self) {
+        let cell = self.lab.index(self.cursor).unwrap();
+        if Some(cell) == self.lab.index(self.start.position) || !self.lab.toggle_obstacle(cell) {
+            return;
+        }
+        self.reset();
+        self.check_loops();
+    }
+
+    /// Handles a key, and returns whether to keep going.
+    fn handle(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char(' ') | KeyCode::Char('s') => self.step(),
+            KeyCode::Char('t') => self.run_to_turn(),
+            KeyCode::Char('e') => self.run_to_end(),
+            KeyCode::Char('r') => self.reset(),
+            KeyCode::Char('o') => self.toggle_obstacle(),
+            KeyCode::Left | KeyCode::Char('h') => self.move_cursor(-1, 0),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(0, 1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(0, -1),
+            KeyCode::Right | KeyCode::Char('l') => self.move_cursor(1, 0),
+            _ => {}
+        }
+        true
+    }
+
+    fn cell_span(&self, cell: usize) -> Span<'static> {
+        let at = self.lab.position(cell);
+        let (glyph, style) = match self.lab.tile(cell) {
+            _ if at == self.guard.position => (
+                self.guard.direction.glyph(),
+                Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            ),
+            Tile::Obstacle => ('#', Style::new().fg(Color::Gray)),
+            Tile::OneWay(_) | Tile::Teleport(_) => (
+                self.map[at.y as usize][at.x as usize],
+                Style::new().fg(Color::Magenta),
+            ),
+            Tile::Floor => match self.trail.glyph(cell) {
+                Some(glyph) => (glyph, Style::new().fg(Color::Cyan)),
+                None if self.visited[cell] => ('.', Style::new().fg(Color::Cyan)),
+                None => ('.', Style::new().fg(Color::DarkGray)),
+            },
+        };
+        let style = if at == self.cursor {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style
+        };
+        Span::styled(glyph.to_string(), style)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [map_area, status_area] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(4)]).areas(frame.area());
+
+        // Only the part of the lab around the cursor is drawn if it does not fit
+        let (width, height) = self.lab.size();
+        let view_width = (map_area.width as usize).saturating_sub(2).min(width);
+        let view_height = (map_area.height as usize).saturating_sub(2).min(height);
+        let left = (self.cursor.x as usize)
+            .saturating_sub(view_width / 2)
+            .min(width - view_width);
+        let top = (self.cursor.y as usize)
+            .saturating_sub(view_height / 2)
+            .min(height - view_height);
+        let lines: Vec<Line> = (top..top + view_height)
+            .map(|y| {
+                (left..left + view_width)
+                    .map(|x| self.cell_span(y * width + x))
+                    .collect()
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Day six ")),
+            map_area,
+        );
+
+        let status = match self.status {
+            Status::Walking => "walking",
+            Status::LeftTheLab => "left the lab",
+            Status::Looping => "stuck in a loop",
+        };
+        let lines = vec![
+            Line::from(format!(
+                "Steps: {}  Visited: {}  Guard: {}  Cursor: x: {}, y: {}",
+                self.steps,
+                self.visited.iter().filter(|&&v| v).count(),
+                status,
+                self.cursor.x,
+                self.cursor.y
+            )),
+            Line::from(format!(
+                "Whole patrol loops: {}  Obstacles that would make a loop: {}",
+                self.loops, self.loop_obstacles
+            )),
+        ];
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(format!(" {} ", HELP))),
+            status_area,
+        );
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Steps through the patrol of the first guard on the map in the terminal, until `q` is
+/// pressed. The map has to have a guard.
+pub fn run(map: &[Vec<char>], lab: Lab, movement: Movement) -> io::Result<()> {
+    let mut stepper = Stepper::new(map, lab, movement);
+    let mut terminal = ratatui::try_init()?;
+    let result = stepper.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stepper() {
+        let map = crate::read_matrix("test.txt");
+        let lab = Lab::from_matrix(&map).unwrap();
+        let mut stepper = Stepper::new(&map, lab, Movement::default());
+        assert!(!stepper.loops);
+        assert_eq!(stepper.loop_obstacles, 6);
+
+        stepper.run_to_turn();
+        assert_eq!(stepper.guard.position, Vector2::new(4, 1));
+        assert_eq!(stepper.steps, 6);
+        stepper.run_to_end();
+        assert_eq!(stepper.status, Status::LeftTheLab);
+        assert_eq!(stepper.visited.iter().filter(|&&v| v).count(), 41);
+
+        // The puzzle's first example of an obstacle that makes a loop
+        for key in [KeyCode::Left, KeyCode::Char('o'), KeyCode::Char('e')] {
+            assert!(stepper.handle(key));
+        }
+        assert!(stepper.loops);
+        assert_eq!(stepper.status, Status::Looping);
+
+        // The guard's own cell cannot be blocked, and taking the obstacle away undoes it
+        stepper.cursor = stepper.start.position;
+        stepper.toggle_obstacle();
+        assert!(stepper.loops);
+        stepper.cursor = Vector2::new(3, 6);
+        stepper.toggle_obstacle();
+        assert!(!stepper.loops);
+        assert_eq!(stepper.loop_obstacles, 6);
+        assert!(!stepper.handle(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn test_draw_follows_cursor() {
+        let map = crate::read_matrix("input.txt");
+        let lab = Lab::from_matrix(&map).unwrap();
+        let mut stepper = Stepper::new(&map, lab, Movement::default());
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(40, 20)).unwrap();
+        terminal.draw(|frame| stepper.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|c| c.symbol())
+            .collect();
+        assert!(screen.contains('^'));
+
+        stepper.cursor = Vector2::new(0, 0);
+        terminal.draw(|frame| stepper.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|c| c.symbol())
+            .collect();
+        assert!(!screen.contains('^'));
+    }
+}
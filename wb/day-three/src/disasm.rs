@@ -0,0 +1,213 @@
+use crate::instruction::{self, Args, Instruction, Rejection, Token};
+use crate::interpreter::Machine;
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Whitespace,
+    UnexpectedByte(u8),
+    OperandDigits(usize),
+    WrongOperandCount(usize),
+    TooManyOperands,
+    Unterminated,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Whitespace => write!(f, "whitespace inside call"),
+            Reason::UnexpectedByte(b) => write!(f, "unexpected {:?}", *b as char),
+            Reason::OperandDigits(digits) => write!(f, "operand with {} digits", digits),
+            Reason::WrongOperandCount(count) => write!(f, "wrong number of operands ({})", count),
+            Reason::TooManyOperands => write!(f, "more operands than any instruction takes"),
+            Reason::Unterminated => write!(f, "memory ends before `)`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Executed {
+        token: Token,
+        enabled: bool,
+        /// `None` once the running total has overflowed.
+        total: Option<u128>,
+    },
+    Rejected {
+        span: Range<usize>,
+        reason: Reason,
+    },
+}
+
+impl Line {
+    fn span(&self) -> &Range<usize> {
+        match self {
+            Line::Executed { token, .. } => &token.span,
+            Line::Rejected { span, .. } => span,
+        }
+    }
+}
+
+/// Looks at a known instruction name at `start` that is not part of any instruction and
+/// explains why the call after it was rejected. Names not followed by `(` are not near misses.
+fn near_miss(
+    bytes: &[u8],
+    start: usize,
+    name: &str,
+    operand_digits: &RangeInclusive<usize>,
+) -> Option<Line> {
+    let after_name = start + name.len();
+    let open = after_name
+        + bytes[after_name..]
+            .iter()
+            .take_while(|b| b.is_ascii_whitespace())
+            .count();
+    if bytes.get(open) != Some(&b'(') {
+        return None;
+    }
+    let (end, reason) = if open > after_name {
+        (open + 1, Reason::Whitespace)
+    } else {
+        match instruction::parse_args(bytes, open, operand_digits) {
+            Args::Complete(args, end) => (end, Reason::WrongOperandCount(args.len())),
+            Args::Invalid(Rejection::UnexpectedByte { at, byte }) if byte.is_ascii_whitespace() => {
+                (at + 1, Reason::Whitespace)
+            }
+            Args::Invalid(Rejection::UnexpectedByte { at, byte }) => {
+                (at + 1, Reason::UnexpectedByte(byte))
+            }
+            Args::Invalid(Rejection::OperandDigits { at, digits }) => {
+                (at + digits, Reason::OperandDigits(digits))
+            }
+            Args::Invalid(Rejection::TooManyOperands { at }) => (at + 1, Reason::TooManyOperands),
+            Args::Incomplete => (bytes.len(), Reason::Unterminated),
+        }
+    };
+    Some(Line::Rejected {
+        span: start..end,
+        reason,
+    })
+}
+
+/// Lists every instruction in memory with the machine state after executing it, interleaved
+/// with the near misses that look like instructions but were rejected.
+pub fn disassemble(input: &str, operand_digits: &RangeInclusive<usize>) -> Vec<Line> {
+    let bytes = input.as_bytes();
+    let tokens = instruction::tokenize(input, operand_digits);
+
+    let mut machine = Machine::<u128>::new();
+    let mut overflowed = false;
+    let mut lines: Vec<Line> = tokens
+        .iter()
+        .map(|token| {
+            overflowed = overflowed || machine.execute(token).is_err();
+            Line::Executed {
+                token: token.clone(),
+                enabled: machine.enabled,
+                total: (!overflowed).then_some(machine.total),
+            }
+        })
+        .collect();
+
+    let mut covered = tokens.iter().map(|t| t.span.clone()).peekable();
+    for start in 0..bytes.len() {
+        while covered.next_if(|span| span.end <= start).is_some() {}
+        if covered.peek().is_some_and(|span| span.contains(&start)) {
+            continue;
+        }
+        let name = Instruction::NAMES
+            .iter()
+            .filter(|name| bytes[start..].starts_with(name.as_bytes()))
+            .max_by_key(|name| name.len());
+        if let Some(line) = name.and_then(|name| near_miss(bytes, start, name, operand_digits)) {
+            lines.push(line);
+        }
+    }
+    lines.sort_by_key(|line| line.span().start);
+    lines
+}
+
+pub fn render(input: &str, lines: &[Line]) -> String {
+    let text = |span: &Range<usize>| {
+        String::from_utf8_lossy(&input.as_bytes()[span.clone()])
+            .chars()
+            .flat_map(|c| {
+                if c.is_control() {
+                    c.escape_default().collect()
+                } else {
+                    vec![c]
+                }
+            })
+            .collect::<String>()
+    };
+    let mut out = format!(
+        "{:>8}  {:<8}  {:>10}  {}\n",
+        "offset", "state", "total", "text"
+    );
+    for line in lines {
+        let row = match line {
+            Line::Executed {
+                token,
+                enabled,
+                total,
+            } => format!(
+                "{:>8}  {:<8}  {:>10}  {}",
+                token.span.start,
+                if *enabled { "enabled" } else { "disabled" },
+                total.map_or("overflow".to_string(), |total| total.to_string()),
+                text(&token.span)
+            ),
+            Line::Rejected { span, reason } => format!(
+                "{:>8}  {:<8}  {:>10}  {:<16}  ; {}",
+                span.start,
+                "rejected",
+                "",
+                text(span),
+                reason
+            ),
+        };
+        out.push_str(&row);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::OPERAND_DIGITS;
+
+    #[test]
+    fn test_near_misses() {
+        let rejected = |input: &str| -> Vec<Reason> {
+            disassemble(input, &OPERAND_DIGITS)
+                .into_iter()
+                .filter_map(|line| match line {
+                    Line::Rejected { reason, .. } => Some(reason),
+                    Line::Executed { .. } => None,
+                })
+                .collect()
+        };
+        assert_eq!(rejected("mul(4*"), vec![Reason::UnexpectedByte(b'*')]);
+        assert_eq!(rejected("mul ( 2 , 4 )"), vec![Reason::Whitespace]);
+        assert_eq!(rejected("mul(1234,5)"), vec![Reason::OperandDigits(4)]);
+        assert_eq!(
+            rejected("mul(12)do(1)"),
+            vec![Reason::WrongOperandCount(1), Reason::WrongOperandCount(1)]
+        );
+        assert_eq!(rejected("mul(1,2,3)"), vec![Reason::TooManyOperands]);
+        assert_eq!(rejected("xmul(2,4)undo()don't()mul[3,7]"), vec![]);
+        assert_eq!(
+            disassemble("mul(1234,5)", &(1..=4)),
+            vec![Line::Executed {
+                token: Token {
+                    instruction: Instruction::Mul(1234, 5),
+                    span: 0..11
+                },
+                enabled: true,
+                total: Some(6170),
+            }]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::ops::{Range, RangeInclusive};
+
+/// How many digits an operand may have unless configured otherwise.
+pub const OPERAND_DIGITS: RangeInclusive<usize> = 1..=3;
+/// Length of the longest instruction name, i.e. how far back from a `(` a name can start.
+const MAX_NAME_LEN: usize = max_len(&Instruction::NAMES);
+
+const fn max_len(names: &[&str]) -> usize {
+    let mut max = 0;
+    let mut i = 0;
+    while i < names.len() {
+        if names[i].len() > max {
+            max = names[i].len();
+        }
+        i += 1;
+    }
+    max
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Mul(u64, u64),
+    Do,
+    Dont,
+}
+
+impl Instruction {
+    pub const NAMES: [&'static str; 3] = ["mul", "do", "don't"];
+    /// The most operands any instruction takes. An argument list that goes on past this is
+    /// rejected without waiting for its end.
+    pub const MAX_OPERANDS: usize = 2;
+
+    /// Maps a call `name(args)` found in memory to an instruction. New instruction kinds only
+    /// need a variant, their name in `NAMES`, an arm here and an arm in `Machine::execute`, and
+    /// a larger `MAX_OPERANDS` if they take more operands.
+    fn from_call(name: &str, args: &[u64]) -> Option<Instruction> {
+        match (name, args) {
+            ("mul", &[x, y]) => Some(Instruction::Mul(x, y)),
+            ("do", &[]) => Some(Instruction::Do),
+            ("don't", &[]) => Some(Instruction::Dont),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub instruction: Instruction,
+    pub span: Range<usize>,
+}
+
+/// Why a call could not be read, with the index of the offending byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    UnexpectedByte { at: usize, byte: u8 },
+    OperandDigits { at: usize, digits: usize },
+    TooManyOperands { at: usize },
+}
+
+fn is_name_byte(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'\''
+}
+
+pub enum Args {
+    Complete(Vec<u64>, usize),
+    Invalid(Rejection),
+    /// The bytes ran out before the argument list was closed or broken.
+    Incomplete,
+}
+
+/// Parses the argument list following the `(` at `open`: zero or more comma separated numbers
+/// with a digit count in `operand_digits` and a closing `)`. On success returns the index just past
+/// the `)`.
+pub fn parse_args(bytes: &[u8], open: usize, operand_digits: &RangeInclusive<usize>) -> Args {
+    let mut args = Vec::new();
+    let mut i = open + 1;
+    if bytes.get(i) == Some(&b')') {
+        return Args::Complete(args, i + 1);
+    }
+    loop {
+        let digits = bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits > *operand_digits.end() {
+            return Args::Invalid(Rejection::OperandDigits { at: i, digits });
+        }
+        if i + digits == bytes.len() {
+            return Args::Incomplete;
+        }
+        if digits == 0 {
+            return Args::Invalid(Rejection::UnexpectedByte {
+                at: i,
+                byte: bytes[i],
+            });
+        }
+        let arg = bytes[i..i + digits].iter().try_fold(0u64, |acc, &b| {
+            acc.checked_mul(10)?.checked_add(u64::from(b - b'0'))
+        });
+        match arg {
+            Some(arg) if operand_digits.contains(&digits) => args.push(arg),
+            _ => return Args::Invalid(Rejection::OperandDigits { at: i, digits }),
+        }
+        i += digits;
+        match bytes[i] {
+            b',' if args.len() == Instruction::MAX_OPERANDS => {
+                return Args::Invalid(Rejection::TooManyOperands { at: i })
+            }
+            b',' => i += 1,
+            b')' => return Args::Complete(args, i + 1),
+            byte => return Args::Invalid(Rejection::UnexpectedByte { at: i, byte }),
+        }
+    }
+}
+
+fn name_start(bytes: &[u8], end: usize) -> usize {
+    end - bytes[..end]
+        .iter()
+        .rev()
+        .take(MAX_NAME_LEN)
+        .take_while(|&&b| is_name_byte(b))
+        .count()
+}
+
+/// Scans a piece of memory for instructions. Unless `at_end` is set, the bytes might continue
+/// past the slice, so a call still open at the end is not rejected. The second value is the
+/// index from which bytes may still belong to an unfinished instruction and must be scanned
+/// again together with what follows.
+pub fn scan(
+    bytes: &[u8],
+    at_end: bool,
+    operand_digits: &RangeInclusive<usize>,
+) -> (Vec<Token>, usize) {
+    let mut tokens = Vec::new();
+
+    for open in bytes
+        .iter()
+        .enumerate()
+        .filter(|(_, &b)| b == b'(')
+        .map(|(i, _)| i)
+    {
+        let start = name_start(bytes, open);
+        // Only a known name can make the call an instruction, so there is no need to wait for
+        // the end of any other argument list
+        let known = |start| {
+            Instruction::NAMES
+                .iter()
+                .any(|name| name.as_bytes() == &bytes[start..open])
+        };
+        if !(start..open).any(known) {
+            continue;
+        }
+        let (args, end) = match parse_args(bytes, open, operand_digits) {
+            Args::Complete(args, end) => (args, end),
+            Args::Invalid(_) => continue,
+            Args::Incomplete if at_end => continue,
+            Args::Incomplete => return (tokens, start),
+        };
+        let token = (start..open).find_map(|start| {
+            let name = std::str::from_utf8(&bytes[start..open]).ok()?;
+            Instruction::from_call(name, &args).map(|instruction| Token {
+                instruction,
+                span: start..end,
+            })
+        });
+        if let Some(token) = token {
+            #[cfg(feature = "dev")]
+            println!("{:?} at {:?}", token.instruction, token.span);
+            tokens.push(token);
+        }
+    }
+    let rest = if at_end {
+        bytes.len()
+    } else {
+        name_start(bytes, bytes.len())
+    };
+    (tokens, rest)
+}
+
+/// Finds every instruction in corrupted memory, in order, with the byte span it occupies.
+/// Memory is scanned for calls `name(args)`, and the longest name suffix that forms a known
+/// instruction wins, so `xmul(2,4)` yields `mul(2,4)` and `undo()` yields `do()`.
+pub fn tokenize(input: &str, operand_digits: &RangeInclusive<usize>) -> Vec<Token> {
+    scan(input.as_bytes(), true, operand_digits).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let expected = vec![
+            Token {
+                instruction: Instruction::Mul(2, 4),
+                span: 1..9,
+            },
+            Token {
+                instruction: Instruction::Dont,
+                span: 20..27,
+            },
+            Token {
+                instruction: Instruction::Mul(5, 5),
+                span: 28..36,
+            },
+            Token {
+                instruction: Instruction::Mul(11, 8),
+                span: 48..57,
+            },
+            Token {
+                instruction: Instruction::Do,
+                span: 59..63,
+            },
+            Token {
+                instruction: Instruction::Mul(8, 5),
+                span: 64..72,
+            },
+        ];
+        assert_eq!(tokenize(input, &OPERAND_DIGITS), expected);
+    }
+}
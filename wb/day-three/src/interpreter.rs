@@ -0,0 +1,154 @@
+use crate::instruction::{Instruction, Token};
+use crate::stream::Scanner;
+use std::fmt;
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+
+/// Unsigned integer type the machine multiplies and sums in. Every operation is checked, so
+/// a result that does not fit is reported instead of wrapping.
+pub trait Word: Copy + fmt::Display {
+    const ZERO: Self;
+    const BITS: u32;
+
+    fn from_operand(operand: u64) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                const ZERO: $t = 0;
+                const BITS: u32 = <$t>::BITS;
+
+                fn from_operand(operand: u64) -> Option<$t> {
+                    <$t>::try_from(operand).ok()
+                }
+
+                fn checked_mul(self, rhs: $t) -> Option<$t> {
+                    <$t>::checked_mul(self, rhs)
+                }
+
+                fn checked_add(self, rhs: $t) -> Option<$t> {
+                    <$t>::checked_add(self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(u32, u64, u128);
+
+#[derive(Debug)]
+pub enum Error {
+    /// The instruction at `offset` does not fit in a `bits` wide word.
+    Overflow {
+        offset: usize,
+        bits: u32,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Overflow { offset, bits } => {
+                write!(f, "overflow of {} bit word at byte {}", bits, offset)
+            }
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// Executes instructions in order. `mul` only contributes while the machine is enabled, which
+/// `do()` and `don't()` toggle.
+#[derive(Debug)]
+pub struct Machine<T> {
+    pub enabled: bool,
+    pub total: T,
+}
+
+impl<T: Word> Machine<T> {
+    pub fn new() -> Machine<T> {
+        Machine {
+            enabled: true,
+            total: T::ZERO,
+        }
+    }
+
+    pub fn execute(&mut self, token: &Token) -> Result<(), Error> {
+        match token.instruction {
+            Instruction::Mul(x, y) => {
+                if self.enabled {
+                    self.total = T::from_operand(x)
+                        .zip(T::from_operand(y))
+                        .and_then(|(x, y)| x.checked_mul(y))
+                        .and_then(|product| self.total.checked_add(product))
+                        .ok_or(Error::Overflow {
+                            offset: token.span.start,
+                            bits: T::BITS,
+                        })?;
+                }
+            }
+            Instruction::Do => self.enabled = true,
+            Instruction::Dont => self.enabled = false,
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self, tokens: &[Token]) -> Result<T, Error> {
+        for token in tokens {
+            self.execute(token)?;
+        }
+        Ok(self.total)
+    }
+
+    pub fn run_stream<R: Read>(
+        &mut self,
+        reader: R,
+        operand_digits: &RangeInclusive<usize>,
+    ) -> Result<T, Error> {
+        for token in Scanner::new(reader, operand_digits.clone()) {
+            self.execute(&token?)?;
+        }
+        Ok(self.total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflow_is_reported() {
+        let tokens = [
+            Token {
+                instruction: Instruction::Mul(999, 999),
+                span: 0..12,
+            },
+            Token {
+                instruction: Instruction::Mul(65536, 65536),
+                span: 12..29,
+            },
+        ];
+        assert_eq!(Machine::<u32>::new().run(&tokens[..1]).unwrap(), 998001);
+        assert!(matches!(
+            Machine::<u32>::new().run(&tokens),
+            Err(Error::Overflow {
+                offset: 12,
+                bits: 32
+            })
+        ));
+        assert_eq!(
+            Machine::<u64>::new().run(&tokens).unwrap(),
+            998001 + (1 << 32)
+        );
+    }
+}
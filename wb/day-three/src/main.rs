@@ -1,5 +1,11 @@
-use regex::Regex;
-use std::fs;
+mod disasm;
+mod instruction;
+mod interpreter;
+mod stream;
+
+use instruction::OPERAND_DIGITS;
+use interpreter::{Error, Machine, Word};
+use std::{env, fs, ops::RangeInclusive, process};
 
 #[cfg(feature = "dev")]
 const FILE_NAME: &str = "test.txt";
@@ -13,28 +19,71 @@ fn read_string_from_file(path: &str) -> String {
     input_string.to_string()
 }
 
-fn main() {
-    let input_string = read_string_from_file(FILE_NAME);
+/// The value after `name`, exiting with a usage error when `name` is the last argument.
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == name)
+        .map(|i| match args.get(i + 1) {
+            Some(value) => value.as_str(),
+            None => {
+                eprintln!("Usage error: {} needs a value", name);
+                process::exit(2)
+            }
+        })
+}
 
-    #[cfg(feature = "dev")]
-    println!("The input string: {}", input_string);
+/// Reads `--digits` as either `MAX` or `MIN-MAX`.
+fn parse_operand_digits(arg: &str) -> RangeInclusive<usize> {
+    let parse = |n: &str| n.parse::<usize>().expect("Invalid digit count");
+    match arg.split_once('-') {
+        Some((min, max)) => parse(min)..=parse(max),
+        None => 1..=parse(arg),
+    }
+}
 
-    let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)|do\(\)|don't\(\)").unwrap();
+fn solve<T: Word>(stream: bool, operand_digits: &RangeInclusive<usize>) -> Result<String, Error> {
+    let total = if stream {
+        let file = fs::File::open(FILE_NAME)?;
+        Machine::<T>::new().run_stream(file, operand_digits)?
+    } else {
+        let input_string = read_string_from_file(FILE_NAME);
 
-    let mut result = 0;
-    let mut mul_activated = true;
-    
-    for caps in re.captures_iter(&input_string) {
         #[cfg(feature = "dev")]
-        println!("{:?}", caps);
-        
-        if (&caps[0] == "do()") | (&caps[0] == "don't()"){
-            mul_activated = &caps[0] == "do()";
-        }else if mul_activated {
-            let x = &caps[1]; 
-            let y = &caps[2];
-            result += x.parse::<i32>().unwrap() * y.parse::<i32>().unwrap();
+        println!("The input string: {}", input_string);
+
+        let tokens = instruction::tokenize(&input_string, operand_digits);
+        Machine::<T>::new().run(&tokens)?
+    };
+    Ok(total.to_string())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let operand_digits =
+        option_value(&args, "--digits").map_or(OPERAND_DIGITS, parse_operand_digits);
+
+    if args.iter().any(|a| a == "--disasm") {
+        let input_string = read_string_from_file(FILE_NAME);
+        let lines = disasm::disassemble(&input_string, &operand_digits);
+        print!("{}", disasm::render(&input_string, &lines));
+        return;
+    }
+
+    let stream = args.iter().any(|a| a == "--stream");
+    let result = match option_value(&args, "--width").unwrap_or("64") {
+        "32" => solve::<u32>(stream, &operand_digits),
+        "64" => solve::<u64>(stream, &operand_digits),
+        "128" => solve::<u128>(stream, &operand_digits),
+        width => {
+            eprintln!("Usage error: --width must be 32, 64 or 128, not {}", width);
+            process::exit(2)
+        }
+    };
+    match result {
+        Ok(result) => println!("Result result: {}", result),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
         }
     }
-    println!("Result result: {}", result);
 }
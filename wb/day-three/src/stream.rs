@@ -0,0 +1,140 @@
+use crate::instruction::{self, Token};
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Reads instructions from any reader without holding the whole memory. The bytes of an
+/// instruction that is cut by a chunk boundary are kept and scanned again with the next chunk.
+/// Spans are byte offsets from the start of the stream.
+pub struct Scanner<R> {
+    reader: R,
+    chunk_size: usize,
+    operand_digits: RangeInclusive<usize>,
+    buffer: Vec<u8>,
+    buffer_offset: usize,
+    tokens: VecDeque<Token>,
+    done: bool,
+}
+
+impl<R: Read> Scanner<R> {
+    pub fn new(reader: R, operand_digits: RangeInclusive<usize>) -> Scanner<R> {
+        Scanner::with_chunk_size(reader, operand_digits, CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(
+        reader: R,
+        operand_digits: RangeInclusive<usize>,
+        chunk_size: usize,
+    ) -> Scanner<R> {
+        Scanner {
+            reader,
+            chunk_size,
+            operand_digits,
+            buffer: Vec::new(),
+            buffer_offset: 0,
+            tokens: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let kept = self.buffer.len();
+        self.buffer.resize(kept + self.chunk_size, 0);
+        let read = loop {
+            match self.reader.read(&mut self.buffer[kept..]) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        let read = read.inspect_err(|_| self.buffer.truncate(kept))?;
+        self.buffer.truncate(kept + read);
+        self.done = read == 0;
+
+        let (tokens, rest) = instruction::scan(&self.buffer, self.done, &self.operand_digits);
+        self.tokens.extend(tokens.into_iter().map(|token| Token {
+            span: token.span.start + self.buffer_offset..token.span.end + self.buffer_offset,
+            ..token
+        }));
+        self.buffer.drain(..rest);
+        self.buffer_offset += rest;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Scanner<R> {
+    type Item = io::Result<Token>;
+
+    fn next(&mut self) -> Option<io::Result<Token>> {
+        while self.tokens.is_empty() && !self.done {
+            if let Err(e) = self.fill() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        self.tokens.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::OPERAND_DIGITS;
+
+    #[test]
+    fn test_scanner_matches_tokenize() {
+        let input =
+            "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))".repeat(3);
+        let expected = instruction::tokenize(&input, &OPERAND_DIGITS);
+        for chunk_size in 1..=input.len() {
+            let tokens: Vec<Token> =
+                Scanner::with_chunk_size(input.as_bytes(), OPERAND_DIGITS, chunk_size)
+                    .collect::<io::Result<_>>()
+                    .unwrap();
+            assert_eq!(tokens, expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_scanner_drops_long_argument_lists() {
+        // Neither call can become an instruction, so the scanner must not hold on to them
+        for name in ["mul", "xyz"] {
+            let memory = format!("{}({}", name, "1,".repeat(1 << 20));
+            let mut scanner = Scanner::with_chunk_size(memory.as_bytes(), OPERAND_DIGITS, 4096);
+            while !scanner.done {
+                scanner.fill().unwrap();
+                assert!(
+                    scanner.buffer.len() < 16,
+                    "{} kept {}",
+                    name,
+                    scanner.buffer.len()
+                );
+            }
+            assert!(scanner.tokens.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_scanner_on_generated_memory() {
+        let alphabet = b"mul(),1234567890don't()x ";
+        let mut state: u64 = 0x2024;
+        let memory: String = (0..1 << 21)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                alphabet[(state >> 33) as usize % alphabet.len()] as char
+            })
+            .collect();
+        let expected = instruction::tokenize(&memory, &OPERAND_DIGITS);
+        assert!(!expected.is_empty());
+        for chunk_size in [7, 4096, CHUNK_SIZE] {
+            let tokens: Vec<Token> =
+                Scanner::with_chunk_size(memory.as_bytes(), OPERAND_DIGITS, chunk_size)
+                    .collect::<io::Result<_>>()
+                    .unwrap();
+            assert_eq!(tokens, expected);
+        }
+    }
+}
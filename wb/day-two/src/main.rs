@@ -1,4 +1,5 @@
-use std::fs;
+use serde::Serialize;
+use std::{env, fs};
 
 const MAX_DIFF: u32 = 3;
 const MIN_DIFF: u32 = 1;
@@ -26,36 +27,13 @@ fn read_vecs_from_file(path: &str) -> Vec<Vec<i32>> {
     vec_matrix
 }
 
-fn has_safe_diffs(report: &Vec<i32>) -> bool {
-    let abs_diffs = report.windows(2).map(|w| w[0].abs_diff(w[1]));
-    let max_diff = abs_diffs.clone().max().unwrap();
-    let min_diff = abs_diffs.min().unwrap();
-
-    return (max_diff <= MAX_DIFF) & (min_diff >= MIN_DIFF);
-}
-
-fn is_monotonic(report: &Vec<i32>) -> bool {
-    let decreasing = report.windows(2).all(|window| window[0] > window[1]);
-    let increasing = report.windows(2).all(|window| window[0] < window[1]);
-    decreasing | increasing
-}
-
 fn get_safety_vecs(inp_matrix: &Vec<Vec<i32>>) -> (Vec<bool>, Vec<bool>) {
     let mut safe_vec_fst_star = Vec::new();
     let mut safe_vec_snd_star = Vec::new();
     for x in inp_matrix {
-        let is_safe = has_safe_diffs(&x) & is_monotonic(&x);
-        safe_vec_fst_star.push(is_safe);
-        if !is_safe {
-            let dampened_reports_matrix = create_all_problem_dampened_reports(x);
-            let safe_reports: Vec<_> = dampened_reports_matrix
-                .iter()
-                .filter(|dp| (has_safe_diffs(dp) & is_monotonic(dp)))
-                .collect();
-            safe_vec_snd_star.push(safe_reports.len() != 0);
-        }else {
-            safe_vec_snd_star.push(is_safe);
-        }
+        let diagnosis = diagnose_report(x);
+        safe_vec_fst_star.push(diagnosis == Diagnosis::Safe);
+        safe_vec_snd_star.push(!matches!(diagnosis, Diagnosis::Unsafe { .. }));
     }
     (safe_vec_fst_star, safe_vec_snd_star)
 }
@@ -77,6 +55,160 @@ fn create_all_problem_dampened_reports(report: &Vec<i32>) -> Vec<Vec<i32>> {
     return dampened_reports;
 }
 
+/// The first rule a report breaks. `index` is the position of the first level of the offending
+/// pair, `from` and `to` are the two levels.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Violation {
+    StepTooLarge {
+        index: usize,
+        from: i32,
+        to: i32,
+        diff: u32,
+    },
+    StepTooSmall {
+        index: usize,
+        from: i32,
+        to: i32,
+        diff: u32,
+    },
+    DirectionChange {
+        index: usize,
+        from: i32,
+        to: i32,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+enum Diagnosis {
+    Safe,
+    DampenedSafe {
+        violation: Violation,
+        removed_index: usize,
+        removed_value: i32,
+    },
+    Unsafe {
+        violation: Violation,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct ReportDiagnosis {
+    report: usize,
+    levels: Vec<i32>,
+    #[serde(flatten)]
+    diagnosis: Diagnosis,
+}
+
+/// The one safety rule: a report is safe when it breaks nothing here, both for `--explain` and
+/// for the counts.
+fn first_violation(report: &[i32]) -> Option<Violation> {
+    let increasing = report.len() > 1 && report[0] < report[1];
+    for (index, w) in report.windows(2).enumerate() {
+        let (from, to) = (w[0], w[1]);
+        let diff = from.abs_diff(to);
+        if diff > MAX_DIFF {
+            return Some(Violation::StepTooLarge {
+                index,
+                from,
+                to,
+                diff,
+            });
+        }
+        if diff < MIN_DIFF {
+            return Some(Violation::StepTooSmall {
+                index,
+                from,
+                to,
+                diff,
+            });
+        }
+        if (from < to) != increasing {
+            return Some(Violation::DirectionChange { index, from, to });
+        }
+    }
+    None
+}
+
+fn diagnose_report(report: &[i32]) -> Diagnosis {
+    let Some(violation) = first_violation(report) else {
+        return Diagnosis::Safe;
+    };
+    let removed = create_all_problem_dampened_reports(&report.to_vec())
+        .iter()
+        .position(|dp| first_violation(dp).is_none());
+    match removed {
+        Some(i) => Diagnosis::DampenedSafe {
+            violation,
+            removed_index: i,
+            removed_value: report[i],
+        },
+        None => Diagnosis::Unsafe { violation },
+    }
+}
+
+fn diagnose_reports(inp_matrix: &[Vec<i32>]) -> Vec<ReportDiagnosis> {
+    inp_matrix
+        .iter()
+        .enumerate()
+        .map(|(i, levels)| ReportDiagnosis {
+            report: i + 1,
+            levels: levels.clone(),
+            diagnosis: diagnose_report(levels),
+        })
+        .collect()
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Violation::StepTooLarge {
+                index,
+                from,
+                to,
+                diff,
+            } => write!(
+                f,
+                "step too large at index {}: {} -> {} (diff {} > {})",
+                index, from, to, diff, MAX_DIFF
+            ),
+            Violation::StepTooSmall {
+                index,
+                from,
+                to,
+                diff,
+            } => write!(
+                f,
+                "step too small at index {}: {} -> {} (diff {} < {})",
+                index, from, to, diff, MIN_DIFF
+            ),
+            Violation::DirectionChange { index, from, to } => {
+                write!(f, "direction change at index {}: {} -> {}", index, from, to)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ReportDiagnosis {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Report {} {:?}: ", self.report, self.levels)?;
+        match &self.diagnosis {
+            Diagnosis::Safe => write!(f, "safe"),
+            Diagnosis::DampenedSafe {
+                violation,
+                removed_index,
+                removed_value,
+            } => write!(
+                f,
+                "unsafe ({}), safe after removing level {} (value {})",
+                violation, removed_index, removed_value
+            ),
+            Diagnosis::Unsafe { violation } => write!(f, "unsafe ({})", violation),
+        }
+    }
+}
+
 fn main() {
     #[cfg(feature = "dev")]
     const FILE_NAME: &str = "test.txt";
@@ -85,6 +217,20 @@ fn main() {
     const FILE_NAME: &str = "input.txt";
 
     let vec_matrix = read_vecs_from_file(FILE_NAME);
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--explain") {
+        let diagnoses = diagnose_reports(&vec_matrix);
+        if args.iter().any(|a| a == "--json") {
+            println!("{}", serde_json::to_string_pretty(&diagnoses).unwrap());
+        } else {
+            for d in &diagnoses {
+                println!("{}", d);
+            }
+        }
+        return;
+    }
+
     let (safety_vec_fst_star, safety_vec_snd_star) = get_safety_vecs(&vec_matrix);
     let num_safe_reports_fst_star =
         safety_vec_fst_star
@@ -105,3 +251,53 @@ fn main() {
         num_safe_reports_snd_star
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnose_report() {
+        assert_eq!(diagnose_report(&[7, 6, 4, 2, 1]), Diagnosis::Safe);
+        assert_eq!(
+            diagnose_report(&[1, 3, 2, 4, 5]),
+            Diagnosis::DampenedSafe {
+                violation: Violation::DirectionChange {
+                    index: 1,
+                    from: 3,
+                    to: 2
+                },
+                removed_index: 1,
+                removed_value: 3,
+            }
+        );
+        assert_eq!(
+            diagnose_report(&[9, 7, 6, 2, 1]),
+            Diagnosis::Unsafe {
+                violation: Violation::StepTooLarge {
+                    index: 2,
+                    from: 6,
+                    to: 2,
+                    diff: 4
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_safety_vecs_follow_diagnosis() {
+        let reports = vec![
+            vec![7, 6, 4, 2, 1],
+            vec![1, 3, 2, 4, 5],
+            vec![9, 7, 6, 2, 1],
+            vec![5],
+        ];
+        assert_eq!(
+            get_safety_vecs(&reports),
+            (
+                vec![true, false, false, true],
+                vec![true, true, false, true]
+            )
+        );
+    }
+}